@@ -0,0 +1,1038 @@
+use std::{env, fs, io};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+
+use encoding_rs::Encoding;
+
+use error::Error;
+use field::Format;
+use input;
+use printer::Colors;
+use walk::WalkOptions;
+
+pub const USAGE: &str = "\
+Usage: minigrep [OPTIONS] QUERY PATH...
+       minigrep [OPTIONS] -e QUERY... PATH...
+       minigrep [OPTIONS] -f FILE PATH...
+       minigrep index [OPTIONS] DIR...
+
+Search for QUERY in each file PATH, or in every file below PATH when it is a
+directory. Files are searched in parallel. Without a PATH, or when PATH is
+`-`, standard input is searched.
+
+`minigrep index DIR` writes a trigram index of the files below DIR (or `.`)
+to DIR/.minigrep-index, rereading only files changed since the last run.
+With --use-index, a search of DIR then skips files the index rules out;
+files changed since they were indexed are always searched.
+
+Options:
+  -e, --regexp QUERY        search for QUERY; repeat to search for several
+  -f, --file FILE           search for each line of FILE
+  -E, --regex               treat QUERY as a regular expression
+  -U, --multiline           let matches span lines (`\\n` matches a line break);
+                            every line a match touches is selected
+  -i, --ignore-case         match case-insensitively
+  -s, --case-sensitive      match case-sensitively, even if CASE_INSENSITIVE is set
+  -S, --smart-case          match case-insensitively unless QUERY has uppercase
+      --fuzzy NUM           select lines with a part within NUM typos (edits) of
+                            QUERY, closest first unless context or -v is asked for
+  -w, --word-regexp         match QUERY only as a whole word
+  -x, --line-regexp         match QUERY only against whole lines
+      --parse FORMAT        parse each line as a `csv` or `json` record and match
+                            QUERY against the field --field names only
+      --field FIELD         a CSV column, by number (from 1) or by its name in
+                            the header line, or a JSON path like user.name or
+                            tags.0
+      --delimiter CHAR      separate CSV fields with CHAR (default: `,`; `\\t`
+                            for a tab)
+  -v, --invert-match        select lines that do not match
+  -n, --line-number         prefix each line with its line number
+  -b, --byte-offset         prefix each line with the byte offset of its start
+      --column              prefix each match with the column it starts at
+  -r, --replace TEXT        print selected lines with each match replaced by
+                            TEXT; with -E, $1 or ${name} insert a capture group
+      --in-place            write the replacements back to the files instead
+      --backup SUFFIX       with --in-place, keep each original as FILE+SUFFIX
+  -A, --after-context NUM   print NUM lines of context after each match
+  -B, --before-context NUM  print NUM lines of context before each match
+  -C, --context NUM         print NUM lines of context around each match
+  -c, --count               print only a count of selected lines per file
+  -l, --files-with-matches  print only the names of files with selected lines
+  -L, --files-without-match print only the names of files without selected lines
+  -m, --max-count NUM       stop searching a file after NUM selected lines
+      --color WHEN          highlight matches: auto (the default), always or never
+      --colors PART:SGR     color PART (path, line, separator or match) with the
+                            SGR parameters SGR, e.g. --colors 'match:1;33'
+      --json                print results as JSON Lines
+      --sort path           print files in path order instead of as they finish
+  -j, --threads NUM         search NUM files at a time (default: one per CPU)
+  -z, --search-zip          decompress gzip, bzip2 and xz input
+  -a, --text                search binary files as if they were text
+      --encoding LABEL      decode input from LABEL (e.g. latin1, utf-16le);
+                            by default UTF-16 is detected from a byte order mark
+      --hidden              search hidden files and directories
+      --no-ignore           don't respect .gitignore and .ignore files
+  -g, --glob GLOB           search only files matching GLOB, or with a leading
+                            `!` skip them; repeatable, later globs win
+  -t, --type TYPE           search only files of TYPE (e.g. rust, js, md)
+  -T, --type-not TYPE       skip files of TYPE
+      --type-add NAME:GLOB  add GLOB to file type NAME, defining it if needed
+      --type-list           print the known file types and exit
+      --watch               keep running: search what is appended to the files
+                            as it arrives (like `tail -f | grep`) and search
+                            files again from the start when they are rewritten
+      --use-index           skip files that the index of a directory PATH
+                            shows can't match; used for literal queries
+      --no-config           ignore the config file
+  -h, --help                print this help and exit
+  -V, --version             print version information and exit
+
+Short flags can be combined (`-inv`) and `--` ends option parsing.
+A flag set earlier, e.g. in the config file, is turned off again by the
+same long option with `no-` in front (`--no-line-number`, `--no-json`,
+`--no-hidden`, ...); `--ignore` undoes `--no-ignore`. The same goes for
+--max-count, --fuzzy and --encoding, and `--no-glob`, `--no-type` and
+`--no-type-not` drop every --glob, --type and --type-not before them.
+The exit status is 0 if something was selected, 1 if nothing was and 2 if
+an error occurred.
+
+Setting CASE_INSENSITIVE in the environment makes -i the default, and
+setting NO_COLOR turns off --color=auto.
+
+Default options are read from the file named by MINIGREP_CONFIG, or else
+from minigrep/config in $XDG_CONFIG_HOME (~/.config). It holds one argument
+per line, e.g. `--smart-case`, `--colors=match:1;33` or `--type-add=web:*.html`;
+blank lines and lines starting with `#` are skipped. Options given on the
+command line come after them, so they win. Patterns, and options that turn
+a search into something else (--replace, --in-place, --backup, --watch,
+--parse, --field), can't be set there.
+";
+
+pub const VERSION: &str = concat!("minigrep ", env!("CARGO_PKG_VERSION"));
+
+/// A command line option, looked up by its short or long spelling.
+struct Opt {
+    short: Option<char>,
+    long: &'static str,
+    takes_value: bool,
+}
+
+const OPTIONS: &[Opt] = &[
+    Opt { short: Some('e'), long: "regexp", takes_value: true },
+    Opt { short: Some('f'), long: "file", takes_value: true },
+    Opt { short: Some('E'), long: "regex", takes_value: false },
+    Opt { short: Some('U'), long: "multiline", takes_value: false },
+    Opt { short: Some('i'), long: "ignore-case", takes_value: false },
+    Opt { short: Some('s'), long: "case-sensitive", takes_value: false },
+    Opt { short: Some('S'), long: "smart-case", takes_value: false },
+    Opt { short: None, long: "fuzzy", takes_value: true },
+    Opt { short: Some('w'), long: "word-regexp", takes_value: false },
+    Opt { short: Some('x'), long: "line-regexp", takes_value: false },
+    Opt { short: None, long: "parse", takes_value: true },
+    Opt { short: None, long: "field", takes_value: true },
+    Opt { short: None, long: "delimiter", takes_value: true },
+    Opt { short: Some('v'), long: "invert-match", takes_value: false },
+    Opt { short: Some('n'), long: "line-number", takes_value: false },
+    Opt { short: Some('b'), long: "byte-offset", takes_value: false },
+    Opt { short: None, long: "column", takes_value: false },
+    Opt { short: Some('r'), long: "replace", takes_value: true },
+    Opt { short: None, long: "in-place", takes_value: false },
+    Opt { short: None, long: "backup", takes_value: true },
+    Opt { short: Some('A'), long: "after-context", takes_value: true },
+    Opt { short: Some('B'), long: "before-context", takes_value: true },
+    Opt { short: Some('C'), long: "context", takes_value: true },
+    Opt { short: Some('c'), long: "count", takes_value: false },
+    Opt { short: Some('l'), long: "files-with-matches", takes_value: false },
+    Opt { short: Some('L'), long: "files-without-match", takes_value: false },
+    Opt { short: Some('m'), long: "max-count", takes_value: true },
+    Opt { short: None, long: "color", takes_value: true },
+    Opt { short: None, long: "colors", takes_value: true },
+    Opt { short: None, long: "json", takes_value: false },
+    Opt { short: None, long: "sort", takes_value: true },
+    Opt { short: Some('j'), long: "threads", takes_value: true },
+    Opt { short: Some('z'), long: "search-zip", takes_value: false },
+    Opt { short: Some('a'), long: "text", takes_value: false },
+    Opt { short: None, long: "encoding", takes_value: true },
+    Opt { short: None, long: "hidden", takes_value: false },
+    Opt { short: None, long: "no-ignore", takes_value: false },
+    Opt { short: Some('g'), long: "glob", takes_value: true },
+    Opt { short: Some('t'), long: "type", takes_value: true },
+    Opt { short: Some('T'), long: "type-not", takes_value: true },
+    Opt { short: None, long: "type-add", takes_value: true },
+    Opt { short: None, long: "type-list", takes_value: false },
+    Opt { short: None, long: "watch", takes_value: false },
+    Opt { short: None, long: "use-index", takes_value: false },
+    Opt { short: None, long: "no-config", takes_value: false },
+    Opt { short: Some('h'), long: "help", takes_value: false },
+    Opt { short: Some('V'), long: "version", takes_value: false },
+];
+
+/// Long options that turn off a flag or clear an option, with the option.
+const NEGATIONS: &[(&str, &str)] = &[
+    ("no-regex", "regex"),
+    ("no-multiline", "multiline"),
+    ("no-smart-case", "smart-case"),
+    ("no-word-regexp", "word-regexp"),
+    ("no-line-regexp", "line-regexp"),
+    ("no-invert-match", "invert-match"),
+    ("no-line-number", "line-number"),
+    ("no-byte-offset", "byte-offset"),
+    ("no-column", "column"),
+    ("no-count", "count"),
+    ("no-files-with-matches", "files-with-matches"),
+    ("no-files-without-match", "files-without-match"),
+    ("no-json", "json"),
+    ("no-search-zip", "search-zip"),
+    ("no-text", "text"),
+    ("no-hidden", "hidden"),
+    ("ignore", "no-ignore"),
+    ("no-use-index", "use-index"),
+    ("no-max-count", "max-count"),
+    ("no-fuzzy", "fuzzy"),
+    ("no-encoding", "encoding"),
+    ("no-glob", "glob"),
+    ("no-type", "type"),
+    ("no-type-not", "type-not"),
+];
+
+/// The order files are printed in when several are searched.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sort {
+    /// Whichever file finishes first is printed first.
+    None,
+    Path,
+}
+
+/// Whether output is highlighted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+    /// Only when stdout is a terminal and `NO_COLOR` isn't set.
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Settle `Auto` into `Always` or `Never` for the current stdout.
+    pub fn resolve(self) -> ColorChoice {
+        match self {
+            ColorChoice::Auto => {
+                let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+
+                if io::stdout().is_terminal() && !no_color {
+                    ColorChoice::Always
+                } else {
+                    ColorChoice::Never
+                }
+            },
+            choice => choice,
+        }
+    }
+}
+
+/// What the command line asked minigrep to do.
+// Only one is ever made, so its size doesn't matter.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+pub enum Command {
+    Search(Config),
+    /// List the file types known to `--type`, with the given walk options.
+    TypeList(WalkOptions),
+    /// Build or update the index of each directory, walked with the given
+    /// options.
+    Index(Vec<String>, WalkOptions),
+    Help,
+    Version,
+}
+
+#[derive(Debug)]
+pub struct Config {
+    /// What to search for; a line is selected if any of them matches.
+    pub patterns: Vec<String>,
+    pub paths: Vec<String>,
+    pub case_sensitive: bool,
+    /// Decide `case_sensitive` from whether the patterns have uppercase.
+    pub smart_case: bool,
+    pub regex: bool,
+    /// Match against the whole input instead of line by line.
+    pub multiline: bool,
+    /// Edit distance within which a pattern matches, for approximate search.
+    pub fuzzy: Option<usize>,
+    /// Only match whole words.
+    pub word_regexp: bool,
+    /// Only match whole lines; takes precedence over `word_regexp`.
+    pub line_regexp: bool,
+    /// How lines are parsed into records, for `--field`.
+    pub parse: Option<Format>,
+    /// The field of each record the query is matched against.
+    pub field: Option<String>,
+    /// Separates CSV fields.
+    pub delimiter: u8,
+    pub invert_match: bool,
+    pub line_number: bool,
+    pub byte_offset: bool,
+    pub column: bool,
+    /// Text each match is replaced with in output.
+    pub replace: Option<String>,
+    /// Rewrite files with `replace` applied instead of printing.
+    pub in_place: bool,
+    /// Suffix of the copy kept of each file rewritten in place.
+    pub backup: Option<String>,
+    pub before_context: usize,
+    pub after_context: usize,
+    pub count: bool,
+    pub files_with_matches: bool,
+    pub files_without_match: bool,
+    /// Selected lines after which a file's search stops.
+    pub max_count: Option<usize>,
+    pub color: ColorChoice,
+    pub colors: Colors,
+    pub json: bool,
+    pub sort: Sort,
+    /// Number of files searched at once; 0 picks one per CPU.
+    pub threads: usize,
+    pub search_zip: bool,
+    /// Search and print binary files like any other.
+    pub text: bool,
+    pub encoding: Option<&'static Encoding>,
+    pub walk: WalkOptions,
+    /// Narrow the files of indexed directories with their index.
+    pub use_index: bool,
+    /// Keep searching the files as they change, for `--watch`.
+    pub watch: bool,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            patterns: Vec::new(),
+            paths: Vec::new(),
+            case_sensitive: env::var("CASE_INSENSITIVE").is_err(),
+            smart_case: false,
+            regex: false,
+            multiline: false,
+            fuzzy: None,
+            word_regexp: false,
+            line_regexp: false,
+            parse: None,
+            field: None,
+            delimiter: b',',
+            invert_match: false,
+            line_number: false,
+            byte_offset: false,
+            column: false,
+            replace: None,
+            in_place: false,
+            backup: None,
+            before_context: 0,
+            after_context: 0,
+            count: false,
+            files_with_matches: false,
+            files_without_match: false,
+            max_count: None,
+            color: ColorChoice::Auto,
+            colors: Colors::default(),
+            json: false,
+            sort: Sort::None,
+            threads: 0,
+            search_zip: false,
+            text: false,
+            encoding: None,
+            walk: WalkOptions::default(),
+            use_index: false,
+            watch: false,
+        }
+    }
+}
+
+impl Command {
+    /// Parse a full argument list, program name included.
+    pub fn parse<I>(args: I) -> Result<Command, Error>
+        where I: IntoIterator<Item = String>,
+    {
+        let mut config = Config::default();
+        let mut positional = Vec::new();
+        let mut type_list = false;
+        // Whether -e or -f was given, even if -f read no patterns.
+        let mut patterns_given = false;
+        let mut args = args.into_iter().skip(1).peekable();
+        let index = args.next_if(|arg| arg == "index").is_some();
+
+        while let Some(arg) = args.next() {
+            let parsed = if arg == "--" {
+                positional.extend(args.by_ref());
+                break;
+            } else if let Some(long) = arg.strip_prefix("--") {
+                parse_long(long, &mut args)?
+            } else if let Some(cluster) = arg.strip_prefix('-').filter(|c| !c.is_empty()) {
+                parse_short(cluster, &mut args)?
+            } else {
+                positional.push(arg);
+                continue;
+            };
+
+            for (name, value) in parsed {
+                match name {
+                    "help" => return Ok(Command::Help),
+                    "version" => return Ok(Command::Version),
+                    "type-list" => type_list = true,
+                    _ => {
+                        patterns_given |= name == "regexp" || name == "file";
+                        config.set(name, value)?
+                    },
+                }
+            }
+        }
+
+        config.walk.check()?;
+        if type_list {
+            return Ok(Command::TypeList(config.walk));
+        }
+        if index {
+            if patterns_given {
+                return Err(Error::InvalidArgument("index takes directories, not queries".to_string()));
+            }
+            if positional.is_empty() {
+                positional.push(".".to_string());
+            }
+            return Ok(Command::Index(positional, config.walk));
+        }
+
+        let mut positional = positional.into_iter();
+
+        if !patterns_given {
+            match positional.next() {
+                Some(arg) => config.patterns.push(arg),
+                None => return Err(Error::MissingArgument("query string")),
+            }
+        }
+
+        config.paths = positional.collect();
+        if config.paths.is_empty() {
+            config.paths.push(input::STDIN.to_string());
+        }
+
+        if config.json && (config.count || config.files_with_matches || config.files_without_match) {
+            return Err(Error::InvalidArgument(
+                "--json can't be combined with --count or --files-with(out)-match(es)".to_string()));
+        }
+        if config.fuzzy.is_some() && (config.regex || config.multiline || config.word_regexp || config.line_regexp) {
+            return Err(Error::InvalidArgument(
+                "--fuzzy can't be combined with --regex, --multiline, --word-regexp or --line-regexp".to_string()));
+        }
+        config.check_field()?;
+        if config.json && config.replace.is_some() {
+            return Err(Error::InvalidArgument("--json can't be combined with --replace".to_string()));
+        }
+        // Replacing is done a line at a time, which can't hold a match
+        // that spans lines.
+        if config.multiline && config.replace.is_some() {
+            return Err(Error::InvalidArgument("--replace can't be combined with --multiline".to_string()));
+        }
+
+        config.check_in_place()?;
+        config.check_watch()?;
+
+        Ok(Command::Search(config))
+    }
+}
+
+impl Config {
+    fn check_field(&self) -> Result<(), Error> {
+        let format = match (self.parse, &self.field) {
+            (None, &None) => return Ok(()),
+            (Some(format), &Some(_)) => format,
+            (Some(_), &None) => return Err(Error::InvalidArgument("--parse needs --field".to_string())),
+            (None, &Some(_)) => return Err(Error::InvalidArgument("--field needs --parse".to_string())),
+        };
+
+        if format == Format::Csv && self.field.as_deref() == Some("0") {
+            return Err(Error::InvalidArgument("CSV columns are numbered from 1".to_string()));
+        }
+        if self.multiline || self.replace.is_some() {
+            return Err(Error::InvalidArgument("--field can't be combined with --multiline or --replace".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// `--watch` only works with whole lines of files that grow, and with
+    /// output that makes sense a few lines at a time.
+    fn check_watch(&self) -> Result<(), Error> {
+        if !self.watch {
+            return Ok(());
+        }
+        if self.paths.iter().any(|path| path == input::STDIN) {
+            return Err(Error::InvalidArgument("--watch needs files to watch, not standard input".to_string()));
+        }
+        if self.parse == Some(Format::Csv) && self.field.as_ref().is_some_and(|field| field.parse::<usize>().is_err()) {
+            return Err(Error::InvalidArgument("--watch needs the CSV column of --field by number".to_string()));
+        }
+
+        let conflicts = [
+            ("multiline", self.multiline),
+            ("count", self.count),
+            ("files-with-matches", self.files_with_matches),
+            ("files-without-match", self.files_without_match),
+            ("in-place", self.in_place),
+            ("search-zip", self.search_zip),
+            ("encoding", self.encoding.is_some()),
+        ];
+
+        match conflicts.iter().find(|&&(_, set)| set) {
+            Some(&(name, _)) => Err(Error::InvalidArgument(format!("--watch can't be combined with --{}", name))),
+            None => Ok(()),
+        }
+    }
+
+    fn check_in_place(&self) -> Result<(), Error> {
+        if !self.in_place {
+            return match self.backup {
+                Some(_) => Err(Error::InvalidArgument("--backup needs --in-place".to_string())),
+                None => Ok(()),
+            };
+        }
+
+        if self.replace.is_none() {
+            return Err(Error::InvalidArgument("--in-place needs --replace".to_string()));
+        }
+        if self.paths.iter().any(|path| path == input::STDIN) {
+            return Err(Error::InvalidArgument("--in-place can't rewrite standard input".to_string()));
+        }
+
+        let conflicts = [
+            ("invert-match", self.invert_match),
+            ("count", self.count),
+            ("files-with-matches", self.files_with_matches),
+            ("files-without-match", self.files_without_match),
+            ("search-zip", self.search_zip),
+            ("encoding", self.encoding.is_some()),
+        ];
+
+        match conflicts.iter().find(|&&(_, set)| set) {
+            Some(&(name, _)) => Err(Error::InvalidArgument(format!("--in-place can't be combined with --{}", name))),
+            None => Ok(()),
+        }
+    }
+
+    fn set(&mut self, name: &str, value: Option<String>) -> Result<(), Error> {
+        if let Some(&(_, flag)) = NEGATIONS.iter().find(|&&(negation, _)| negation == name) {
+            self.unset(flag);
+            return Ok(());
+        }
+        let value = value.unwrap_or_default();
+
+        match name {
+            "regexp" => self.patterns.push(value),
+            "file" => {
+                let patterns = fs::read_to_string(&value)
+                    .map_err(|e| Error::reading(value.as_str(), e))?;
+
+                self.patterns.extend(patterns.lines().map(String::from));
+            },
+            "regex" => self.regex = true,
+            "multiline" => self.multiline = true,
+            "ignore-case" => {
+                self.case_sensitive = false;
+                self.smart_case = false;
+            },
+            "case-sensitive" => {
+                self.case_sensitive = true;
+                self.smart_case = false;
+            },
+            "smart-case" => self.smart_case = true,
+            "fuzzy" => self.fuzzy = Some(number(name, &value)?),
+            "word-regexp" => self.word_regexp = true,
+            "line-regexp" => self.line_regexp = true,
+            "invert-match" => self.invert_match = true,
+            "line-number" => self.line_number = true,
+            "byte-offset" => self.byte_offset = true,
+            "column" => self.column = true,
+            "replace" => self.replace = Some(value),
+            "in-place" => self.in_place = true,
+            "backup" => self.backup = Some(value),
+            "after-context" => self.after_context = number(name, &value)?,
+            "before-context" => self.before_context = number(name, &value)?,
+            "context" => {
+                self.after_context = number(name, &value)?;
+                self.before_context = self.after_context;
+            },
+            "count" => self.count = true,
+            "files-with-matches" => self.files_with_matches = true,
+            "files-without-match" => self.files_without_match = true,
+            "max-count" => self.max_count = Some(number(name, &value)?),
+            "color" => {
+                self.color = match value.as_str() {
+                    "auto" => ColorChoice::Auto,
+                    "always" => ColorChoice::Always,
+                    "never" => ColorChoice::Never,
+                    _ => return Err(Error::InvalidArgument(format!("option '--color' expects 'auto', 'always' or 'never', got '{}'", value))),
+                }
+            },
+            "colors" => self.colors.set(&value)?,
+            "json" => self.json = true,
+            "sort" => {
+                self.sort = match value.as_str() {
+                    "none" => Sort::None,
+                    "path" => Sort::Path,
+                    _ => return Err(Error::InvalidArgument(format!("option '--sort' expects 'path' or 'none', got '{}'", value))),
+                }
+            },
+            "threads" => self.threads = number(name, &value)?,
+            "search-zip" => self.search_zip = true,
+            "text" => self.text = true,
+            "encoding" => {
+                self.encoding = match Encoding::for_label(value.as_bytes()) {
+                    Some(encoding) => Some(encoding),
+                    None => return Err(Error::InvalidArgument(format!("unknown encoding '{}'", value))),
+                }
+            },
+            // Only matters before parsing; see `with_config_file`.
+            "no-config" => {},
+            "use-index" => self.use_index = true,
+            "watch" => self.watch = true,
+            "parse" => {
+                self.parse = match value.as_str() {
+                    "csv" => Some(Format::Csv),
+                    "json" => Some(Format::Json),
+                    _ => return Err(Error::InvalidArgument(
+                        format!("option '--parse' expects csv or json, got '{}'", value))),
+                }
+            },
+            "field" => self.field = Some(value),
+            "delimiter" => {
+                self.delimiter = match value.as_bytes() {
+                    b"\\t" => b'\t',
+                    &[byte] if byte.is_ascii() => byte,
+                    _ => return Err(Error::InvalidArgument(
+                        format!("option '--delimiter' expects a single character, got '{}'", value))),
+                }
+            },
+            "hidden" => self.walk.hidden = true,
+            "no-ignore" => self.walk.no_ignore = true,
+            "glob" => self.walk.globs.push(value),
+            "type" => self.walk.types.push(value),
+            "type-not" => self.walk.types_not.push(value),
+            "type-add" => self.walk.type_defs.push(value),
+            _ => unreachable!("option --{} has no handler", name),
+        }
+
+        Ok(())
+    }
+
+    /// Undo `--name`; see `NEGATIONS`.
+    fn unset(&mut self, name: &str) {
+        match name {
+            "regex" => self.regex = false,
+            "multiline" => self.multiline = false,
+            "smart-case" => self.smart_case = false,
+            "word-regexp" => self.word_regexp = false,
+            "line-regexp" => self.line_regexp = false,
+            "invert-match" => self.invert_match = false,
+            "line-number" => self.line_number = false,
+            "byte-offset" => self.byte_offset = false,
+            "column" => self.column = false,
+            "count" => self.count = false,
+            "files-with-matches" => self.files_with_matches = false,
+            "files-without-match" => self.files_without_match = false,
+            "json" => self.json = false,
+            "search-zip" => self.search_zip = false,
+            "text" => self.text = false,
+            "hidden" => self.walk.hidden = false,
+            "no-ignore" => self.walk.no_ignore = false,
+            "use-index" => self.use_index = false,
+            "max-count" => self.max_count = None,
+            "fuzzy" => self.fuzzy = None,
+            "encoding" => self.encoding = None,
+            "glob" => self.walk.globs.clear(),
+            "type" => self.walk.types.clear(),
+            "type-not" => self.walk.types_not.clear(),
+            _ => unreachable!("option --{} can't be undone", name),
+        }
+    }
+}
+
+/// The config file: the one `MINIGREP_CONFIG` names, which has to exist
+/// (set but empty means none), or else `minigrep/config` in the user's
+/// config directory if there is one. Marked `true` if it has to exist.
+fn config_file() -> Option<(PathBuf, bool)> {
+    if let Some(path) = env::var_os("MINIGREP_CONFIG") {
+        return if path.is_empty() { None } else { Some((PathBuf::from(path), true)) };
+    }
+
+    let dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+
+    Some((dir.join("minigrep").join("config"), false))
+}
+
+/// The arguments in a config file, one per line and taken as is, without
+/// blank lines and `#` comments.
+pub fn config_file_args(contents: &str) -> Vec<String> {
+    contents.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+/// `args` with the arguments of the config file inserted after the program
+/// name (and the `index` subcommand), so the ones given on the command line
+/// come later and override them. Nothing is inserted if `--no-config` is among the options.
+pub fn with_config_file<I>(args: I) -> Result<Vec<String>, Error>
+    where I: IntoIterator<Item = String>,
+{
+    let mut args: Vec<String> = args.into_iter().collect();
+    let no_config = args.iter().skip(1).take_while(|arg| *arg != "--").any(|arg| arg == "--no-config");
+
+    let (path, required) = match config_file() {
+        Some(file) if !no_config => file,
+        _ => return Ok(args),
+    };
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(ref e) if !required && e.kind() == io::ErrorKind::NotFound => return Ok(args),
+        Err(e) => return Err(Error::reading(path, e)),
+    };
+
+    // Checked on their own, so a mistake in the file is blamed on it.
+    let file_args = config_file_args(&contents);
+    check_config_file_args(&file_args).map_err(|e| Error::ConfigFile { path, source: Box::new(e) })?;
+
+    let at = if args.get(1).is_some_and(|arg| arg == "index") { 2 } else { args.len().min(1) };
+    args.splice(at..at, file_args);
+
+    Ok(args)
+}
+
+/// Parse the arguments from a config file, which may only set options
+/// that the command line can undo, and that leave a search a search.
+fn check_config_file_args(args: &[String]) -> Result<(), Error> {
+    let mut config = Config::default();
+    let mut rest = args.iter().cloned();
+
+    while let Some(arg) = rest.next() {
+        let parsed = if arg.starts_with("--") && arg.len() > 2 {
+            parse_long(&arg[2..], &mut rest)?
+        } else if arg.starts_with('-') && arg.len() > 1 {
+            parse_short(&arg[1..], &mut rest)?
+        } else {
+            return Err(Error::InvalidArgument(format!("expected an option, got '{}'", arg)));
+        };
+
+        for (name, value) in parsed {
+            match name {
+                "help" | "version" | "type-list" | "no-config" | "regexp" | "file" | "replace" | "in-place"
+                | "backup" | "watch" | "parse" | "field" => return Err(Error::InvalidArgument(
+                    format!("option '--{}' doesn't belong in a config file", name))),
+                _ => config.set(name, value)?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn number(name: &str, value: &str) -> Result<usize, Error> {
+    value.parse()
+        .map_err(|_| Error::InvalidArgument(format!("option '--{}' expects a number, got '{}'", name, value)))
+}
+
+type Parsed = Vec<(&'static str, Option<String>)>;
+
+/// `--name`, `--name=value` or `--name value`.
+fn parse_long<I>(arg: &str, rest: &mut I) -> Result<Parsed, Error>
+    where I: Iterator<Item = String>,
+{
+    let (name, inline) = match arg.find('=') {
+        Some(i) => (&arg[..i], Some(arg[i + 1..].to_string())),
+        None => (arg, None),
+    };
+
+    let opt = match OPTIONS.iter().find(|opt| opt.long == name) {
+        Some(opt) => opt,
+        None => match NEGATIONS.iter().find(|&&(negation, _)| negation == name) {
+            Some(&(negation, _)) if inline.is_none() => return Ok(vec![(negation, None)]),
+            Some(_) => return Err(Error::InvalidArgument(format!("option '--{}' doesn't take a value", name))),
+            None => return Err(Error::InvalidArgument(format!("unknown option '--{}'", name))),
+        },
+    };
+
+    let value = match (opt.takes_value, inline) {
+        (true, Some(value)) => Some(value),
+        (true, None) => match rest.next() {
+            Some(value) => Some(value),
+            None => return Err(Error::InvalidArgument(format!("option '--{}' needs a value", name))),
+        },
+        (false, Some(_)) => return Err(Error::InvalidArgument(format!("option '--{}' doesn't take a value", name))),
+        (false, None) => None,
+    };
+
+    Ok(vec![(opt.long, value)])
+}
+
+/// A cluster of short flags such as `-inv`. An option taking a value
+/// consumes the rest of the cluster (`-A3`) or else the next argument.
+fn parse_short<I>(cluster: &str, rest: &mut I) -> Result<Parsed, Error>
+    where I: Iterator<Item = String>,
+{
+    let mut parsed = Vec::new();
+
+    for (i, c) in cluster.char_indices() {
+        let opt = match OPTIONS.iter().find(|opt| opt.short == Some(c)) {
+            Some(opt) => opt,
+            None => return Err(Error::InvalidArgument(format!("unknown option '-{}'", c))),
+        };
+
+        if !opt.takes_value {
+            parsed.push((opt.long, None));
+            continue;
+        }
+
+        let inline = &cluster[i + c.len_utf8()..];
+        let value = if !inline.is_empty() {
+            inline.to_string()
+        } else {
+            match rest.next() {
+                Some(value) => value,
+                None => return Err(Error::InvalidArgument(format!("option '-{}' needs a value", c))),
+            }
+        };
+
+        parsed.push((opt.long, Some(value)));
+        break;
+    }
+
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::process;
+
+    fn parse(args: &[&str]) -> Result<Command, Error> {
+        Command::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn config(args: &[&str]) -> Config {
+        match parse(args) {
+            Ok(Command::Search(config)) => config,
+            other => panic!("expected a search, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn combined_short_flags() {
+        let config = config(&["minigrep", "-inv", "to", "poem.txt"]);
+
+        assert!(!config.case_sensitive);
+        assert!(config.invert_match);
+        assert!(config.line_number);
+        assert_eq!(vec!["to"], config.patterns);
+        assert_eq!(vec!["poem.txt"], config.paths);
+    }
+
+    #[test]
+    fn double_dash_ends_options() {
+        let config = config(&["minigrep", "-c", "--", "-v", "poem.txt"]);
+
+        assert!(config.count);
+        assert!(!config.invert_match);
+        assert_eq!(vec!["-v"], config.patterns);
+    }
+
+    #[test]
+    fn help_version_and_errors() {
+        assert!(matches!(parse(&["minigrep", "to", "--help"]), Ok(Command::Help)));
+        assert!(matches!(parse(&["minigrep", "-V"]), Ok(Command::Version)));
+        assert_eq!(
+            "unknown option '-K'",
+            parse(&["minigrep", "-nK", "to", "poem.txt"]).unwrap_err().to_string(),
+        );
+        match parse(&["minigrep", "-n"]) {
+            Err(Error::MissingArgument(what)) => assert_eq!("query string", what),
+            other => panic!("expected a missing query, got {:?}", other),
+        }
+        assert_eq!(vec!["-"], config(&["minigrep", "to"]).paths);
+    }
+
+    #[test]
+    fn several_patterns() {
+        let config = config(&["minigrep", "-e", "to", "-Snefrog", "poem.txt", "src"]);
+
+        assert_eq!(vec!["to", "frog"], config.patterns);
+        assert_eq!(vec!["poem.txt", "src"], config.paths);
+        assert!(config.smart_case && config.line_number);
+    }
+
+    #[test]
+    fn empty_patterns_file() {
+        // Nothing to find, rather than the next argument taken for the query.
+        let empty = env::temp_dir().join(format!("minigrep-empty-{}", process::id()));
+        fs::write(&empty, "").unwrap();
+        let none = config(&["minigrep", "-f", empty.to_str().unwrap(), "file"]);
+        assert!(none.patterns.is_empty());
+        assert_eq!(vec!["file"], none.paths);
+        fs::remove_file(&empty).unwrap();
+    }
+
+    #[test]
+    fn option_values() {
+        let split = config(&["minigrep", "-nA2", "-B", "1", "to", "poem.txt"]);
+        assert_eq!((1, 2), (split.before_context, split.after_context));
+        assert!(split.line_number);
+
+        let both = config(&["minigrep", "--context=3", "--sort", "path", "to", "a", "b"]);
+        assert_eq!((3, 3), (both.before_context, both.after_context));
+        assert_eq!(Sort::Path, both.sort);
+        assert_eq!(vec!["a", "b"], both.paths);
+
+        assert_eq!(Some(2), config(&["minigrep", "--fuzzy", "2", "Jhon", "a.log"]).fuzzy);
+        assert_eq!(
+            "--fuzzy can't be combined with --regex, --multiline, --word-regexp or --line-regexp",
+            parse(&["minigrep", "-E", "--fuzzy=1", "Jhon", "a.log"]).unwrap_err().to_string(),
+        );
+
+        assert_eq!(
+            "option '--context' expects a number, got 'x'",
+            parse(&["minigrep", "-Cx", "to", "poem.txt"]).unwrap_err().to_string(),
+        );
+    }
+
+    #[test]
+    fn file_filters() {
+        let filtered = config(&["minigrep", "-g", "*.rs", "--glob=!target/**", "-trust", "-T", "js", "fn", "."]);
+        assert_eq!(vec!["*.rs", "!target/**"], filtered.walk.globs);
+        assert_eq!(vec!["rust"], filtered.walk.types);
+        assert_eq!(vec!["js"], filtered.walk.types_not);
+
+        match parse(&["minigrep", "--type-list", "--type-add", "docs:*.md"]) {
+            Ok(Command::TypeList(options)) => assert_eq!(vec!["docs:*.md"], options.type_defs),
+            other => panic!("expected a type list, got {:?}", other),
+        }
+        assert_eq!(
+            "unrecognized file type: cobol-ish",
+            parse(&["minigrep", "-t", "cobol-ish", "fn", "."]).unwrap_err().to_string(),
+        );
+    }
+
+    #[test]
+    fn field_options() {
+        let tsv = config(&["minigrep", "--parse", "csv", "--delimiter", "\\t", "--field", "user", "root", "users.tsv"]);
+        assert_eq!((Some(Format::Csv), b'\t'), (tsv.parse, tsv.delimiter));
+        assert_eq!(Some("user"), tsv.field.as_deref());
+
+        let error = |args: &[&str]| parse(args).unwrap_err().to_string();
+        assert_eq!("--field needs --parse", error(&["minigrep", "--field", "user.name", "bob", "app.log"]));
+        assert_eq!("CSV columns are numbered from 1", error(&["minigrep", "--parse=csv", "--field=0", "x", "a.csv"]));
+        assert_eq!(
+            "option '--parse' expects csv or json, got 'xml'",
+            error(&["minigrep", "--parse", "xml", "--field", "a", "x", "a.xml"]),
+        );
+    }
+
+    #[test]
+    fn watch_needs_growing_files() {
+        assert!(config(&["minigrep", "--watch", "-n", "error", "app.log"]).watch);
+        assert_eq!(
+            "--watch needs files to watch, not standard input",
+            parse(&["minigrep", "--watch", "error"]).unwrap_err().to_string(),
+        );
+        assert_eq!(
+            "--watch can't be combined with --count",
+            parse(&["minigrep", "--watch", "-c", "error", "app.log"]).unwrap_err().to_string(),
+        );
+    }
+
+    #[test]
+    fn index_command() {
+        match parse(&["minigrep", "index", "--hidden", "src", "tests"]) {
+            Ok(Command::Index(dirs, options)) => {
+                assert_eq!(vec!["src", "tests"], dirs);
+                assert!(options.hidden);
+            },
+            other => panic!("expected an index command, got {:?}", other),
+        }
+        match parse(&["minigrep", "index"]) {
+            Ok(Command::Index(dirs, _)) => assert_eq!(vec!["."], dirs),
+            other => panic!("expected an index command, got {:?}", other),
+        }
+        assert!(parse(&["minigrep", "index", "-e", "fn", "src"]).is_err());
+
+        // Only as the first argument; elsewhere it is just a query.
+        assert_eq!(vec!["index"], config(&["minigrep", "-n", "index", "README.md"]).patterns);
+        assert!(config(&["minigrep", "--use-index", "fn", "."]).use_index);
+    }
+
+    #[test]
+    fn config_file_defaults() {
+        let defaults = config_file_args("# team setup\n--smart-case\n\n  --colors=match:1;33\n-C\n2\n--type-add=web:*.html\n");
+        assert_eq!(vec!["--smart-case", "--colors=match:1;33", "-C", "2", "--type-add=web:*.html"], defaults);
+
+        let mut args = vec!["minigrep"];
+        args.extend(defaults.iter().map(String::as_str));
+        args.extend(&["-A1", "-s", "Frog", "poem.txt"]);
+        let merged = config(&args);
+
+        assert_eq!((2, 1), (merged.before_context, merged.after_context));
+        assert!(merged.case_sensitive && !merged.smart_case);
+        assert_eq!("1;33", merged.colors.matched);
+        assert_eq!(vec!["web:*.html"], merged.walk.type_defs);
+
+        assert_eq!(
+            "option '--colors' expects PART:SGR, such as 'match:1;33', got 'match:yellow'",
+            parse(&["minigrep", "--colors", "match:yellow", "to"]).unwrap_err().to_string(),
+        );
+
+        // Flags from the file can be turned off on the command line.
+        let off = config(&["minigrep", "-n", "--hidden", "--json", "--no-line-number", "--no-hidden", "--no-json", "to"]);
+        assert!(!off.line_number && !off.walk.hidden && !off.json);
+        assert!(!config(&["minigrep", "--no-ignore", "--ignore", "to"]).walk.no_ignore);
+        assert!(parse(&["minigrep", "--no-json=yes", "to"]).is_err());
+
+        let check = |args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            check_config_file_args(&args).map_err(|e| e.to_string())
+        };
+        assert_eq!(Ok(()), check(&defaults.iter().map(String::as_str).collect::<Vec<_>>()));
+        assert_eq!(Err("unknown option '--smart-cse'".to_string()), check(&["--smart-cse"]));
+        assert_eq!(Err("expected an option, got 'Frog'".to_string()), check(&["-i", "Frog"]));
+        assert_eq!(Err("option '--help' doesn't belong in a config file".to_string()), check(&["-h"]));
+        assert_eq!(Err("option '--regexp' doesn't belong in a config file".to_string()), check(&["-e", "TODO"]));
+        assert_eq!(Err("option '--in-place' doesn't belong in a config file".to_string()), check(&["--in-place"]));
+        assert_eq!(Err("option '--replace' doesn't belong in a config file".to_string()), check(&["-rX"]));
+
+        // So can values the file gives.
+        let cleared = config(&["minigrep", "--fuzzy=1", "-m3", "-g*.rs", "-trust", "--no-fuzzy", "--no-max-count",
+                               "--no-glob", "--no-type", "-E", "to"]);
+        assert_eq!((None, None), (cleared.fuzzy, cleared.max_count));
+        assert!(cleared.walk.globs.is_empty() && cleared.walk.types.is_empty());
+
+        let blamed = Error::ConfigFile {
+            path: "minigreprc".into(),
+            source: Box::new(Error::InvalidArgument("unknown option '--smart-cse'".to_string())),
+        };
+        assert_eq!("config file minigreprc: unknown option '--smart-cse'", ::error::report(&blamed));
+    }
+
+    #[test]
+    fn replace_in_place() {
+        let rewrite = config(&["minigrep", "-r", "you", "--in-place", "--backup=.bak", "thou", "poem.txt"]);
+        assert_eq!(Some("you".to_string()), rewrite.replace);
+        assert!(rewrite.in_place);
+        assert_eq!(Some(".bak".to_string()), rewrite.backup);
+
+        let error = |args: &[&str]| parse(args).unwrap_err().to_string();
+        assert_eq!("--in-place needs --replace", error(&["minigrep", "--in-place", "thou", "poem.txt"]));
+        assert_eq!("--backup needs --in-place", error(&["minigrep", "-rx", "--backup", "~", "thou", "poem.txt"]));
+        assert_eq!("--in-place can't rewrite standard input", error(&["minigrep", "-rx", "--in-place", "thou"]));
+        assert_eq!(
+            "--in-place can't be combined with --invert-match",
+            error(&["minigrep", "-vrx", "--in-place", "thou", "poem.txt"]),
+        );
+        assert_eq!("--replace can't be combined with --multiline", error(&["minigrep", "-U", "-rx", "r\nb", "poem.txt"]));
+    }
+}
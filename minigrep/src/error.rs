@@ -0,0 +1,108 @@
+use std::{error, fmt, io};
+use std::path::PathBuf;
+
+use regex;
+
+/// Everything that can go wrong in minigrep.
+#[derive(Debug)]
+pub enum Error {
+    /// A required argument wasn't given; holds what was missing.
+    MissingArgument(&'static str),
+    /// An option that doesn't exist, or a value an option can't take.
+    InvalidArgument(String),
+    InvalidPattern {
+        pattern: String,
+        source: regex::Error,
+    },
+    /// Reading or writing failed. `path` is the file involved, if any.
+    Io {
+        path: Option<PathBuf>,
+        source: io::Error,
+    },
+    /// The input couldn't be decoded, e.g. a corrupt compressed file.
+    Decoding {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// Something is wrong with an argument in the config file at `path`.
+    ConfigFile {
+        path: PathBuf,
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    /// Classify an error that happened while reading `path`.
+    pub fn reading<P: Into<PathBuf>>(path: P, source: io::Error) -> Error {
+        let path = path.into();
+
+        match source.kind() {
+            io::ErrorKind::InvalidData | io::ErrorKind::InvalidInput => {
+                Error::Decoding { path, source }
+            },
+            _ => Error::Io { path: Some(path), source },
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::MissingArgument(what) => write!(f, "Didn't get a {}", what),
+            Error::InvalidArgument(ref message) => write!(f, "{}", message),
+            Error::InvalidPattern { ref pattern, .. } => write!(f, "invalid pattern '{}'", pattern),
+            Error::Io { path: Some(ref path), .. } => write!(f, "{}", path.display()),
+            Error::Io { path: None, .. } => write!(f, "I/O error"),
+            Error::Decoding { ref path, .. } => write!(f, "{}: couldn't decode input", path.display()),
+            Error::ConfigFile { ref path, .. } => write!(f, "config file {}", path.display()),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::MissingArgument(_) | Error::InvalidArgument(_) => None,
+            Error::InvalidPattern { ref source, .. } => Some(source),
+            Error::Io { ref source, .. } | Error::Decoding { ref source, .. } => Some(source),
+            Error::ConfigFile { ref source, .. } => Some(&**source),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(source: io::Error) -> Error {
+        Error::Io { path: None, source }
+    }
+}
+
+/// `error` followed by each of its sources, separated by `: `.
+pub fn report(error: &dyn error::Error) -> String {
+    let mut report = error.to_string();
+    let mut source = error.source();
+
+    while let Some(error) = source {
+        report.push_str(": ");
+        report.push_str(&error.to_string());
+        source = error.source();
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reports_source_chain() {
+        let pattern = "(".to_string();
+        let source = regex::Regex::new(&pattern).unwrap_err();
+        let error = Error::InvalidPattern { pattern, source };
+
+        assert!(report(&error).starts_with("invalid pattern '(': regex parse error"));
+
+        let error = Error::reading("app.log.gz", io::Error::new(io::ErrorKind::InvalidInput, "corrupt deflate stream"));
+        assert_eq!("app.log.gz: couldn't decode input: corrupt deflate stream", report(&error));
+    }
+}
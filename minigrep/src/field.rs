@@ -0,0 +1,304 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io::prelude::*;
+use std::path::Path;
+
+use memchr::memchr;
+use serde_json::value::RawValue;
+
+use config::Config;
+use error::Error;
+use search::{trim_terminator, Query};
+use searcher::Matcher;
+
+/// How each line is parsed into a record, for `--parse`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// Delimiter-separated values, one record per line.
+    Csv,
+    /// JSON Lines: a JSON value per line.
+    Json,
+}
+
+/// Which field of a record the query is matched against.
+#[derive(Debug, Clone, PartialEq)]
+enum Selector {
+    /// A 0-based CSV column.
+    Column(usize),
+    /// A CSV column named in the header line of each input; see `read_header`.
+    Name(String),
+    /// The object keys and array indexes leading to a JSON value.
+    Path(Vec<String>),
+}
+
+/// The field `--field` picks out of each line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    selector: Selector,
+    delimiter: u8,
+}
+
+/// Where a field is in its line, and what it holds once unquoted.
+struct Located<'l> {
+    start: usize,
+    end: usize,
+    value: Cow<'l, str>,
+    /// Offset of `value` in the line, if it is there as is.
+    verbatim: Option<usize>,
+}
+
+impl Field {
+    /// The field `config` asks for, if any. Column numbers are 1-based on
+    /// the command line; anything else names a column.
+    pub fn new(config: &Config) -> Option<Field> {
+        let field = config.field.as_ref()?;
+        let selector = match config.parse? {
+            Format::Csv => match field.parse::<usize>() {
+                Ok(column) if column > 0 => Selector::Column(column - 1),
+                _ => Selector::Name(field.clone()),
+            },
+            Format::Json => Selector::Path(field.split('.').map(String::from).collect()),
+        };
+
+        Some(Field { selector, delimiter: config.delimiter })
+    }
+
+    /// Whether the field is a CSV column that has to be looked up in a header.
+    pub fn is_named_column(&self) -> bool {
+        matches!(self.selector, Selector::Name(_))
+    }
+
+    fn locate<'l>(&self, line: &'l str) -> Option<Located<'l>> {
+        match self.selector {
+            Selector::Column(column) => CsvFields::new(line, self.delimiter).nth(column),
+            // Not looked up yet, so nothing can match.
+            Selector::Name(_) => None,
+            Selector::Path(ref path) => json_field(line, path),
+        }
+    }
+
+    /// The field's value in `line`, unquoted, if it has one.
+    pub fn value<'l>(&self, line: &'l str) -> Option<Cow<'l, str>> {
+        self.locate(line).map(|located| located.value)
+    }
+
+    /// Where `query` matches the field in `line`: the match itself if the
+    /// field's value is in the line as is, otherwise the whole field.
+    pub fn find<M: Matcher + ?Sized>(&self, query: &M, line: &str) -> Option<(usize, usize)> {
+        let located = self.locate(line)?;
+        query.find(&located.value).map(|found| located.translate(found))
+    }
+
+    /// Every match of `query` in the field in `line`, as in `find`.
+    pub fn find_all<M: Matcher + ?Sized>(&self, query: &M, line: &str) -> Vec<(usize, usize)> {
+        let located = match self.locate(line) {
+            Some(located) => located,
+            None => return Vec::new(),
+        };
+        let mut found: Vec<_> = query.find_all(&located.value).into_iter()
+            .map(|found| located.translate(found))
+            .collect();
+
+        found.dedup();
+        found
+    }
+}
+
+impl<'l> Located<'l> {
+    fn translate(&self, (start, end): (usize, usize)) -> (usize, usize) {
+        match self.verbatim {
+            Some(offset) => (offset + start, offset + end),
+            None => (self.start, self.end),
+        }
+    }
+}
+
+/// For a CSV column named by `--field`, `query` with the column looked up
+/// in the header line `reader` is at, which is read off it; along with the
+/// header's length in bytes. `None` if the query doesn't need a header or
+/// the input is empty. `path` is what `reader` reads, for errors.
+pub fn read_header<R: BufRead>(query: &Query, path: &Path, reader: &mut R) -> Result<Option<(Query, usize)>, Error> {
+    let (inner, field) = match *query {
+        Query::Field(ref inner, ref field) if field.is_named_column() => (inner, field),
+        _ => return Ok(None),
+    };
+    let name = match field.selector {
+        Selector::Name(ref name) => name,
+        _ => unreachable!("checked above"),
+    };
+
+    let mut header = Vec::new();
+    if reader.read_until(b'\n', &mut header).map_err(|e| Error::reading(path, e))? == 0 {
+        return Ok(None);
+    }
+
+    let line = String::from_utf8_lossy(&header);
+    let column = CsvFields::new(trim_terminator(&line), field.delimiter)
+        .position(|column| column.value == name.as_str())
+        .ok_or_else(|| Error::InvalidArgument(
+            format!("{}: no column named '{}' in the header", path.display(), name)))?;
+    let field = Field { selector: Selector::Column(column), delimiter: field.delimiter };
+
+    Ok(Some((Query::Field(inner.clone(), field), header.len())))
+}
+
+/// The fields of a CSV line. A quoted field may hold the delimiter, with
+/// `""` standing for a quote; quotes elsewhere are taken as is.
+struct CsvFields<'l> {
+    line: &'l str,
+    delimiter: u8,
+    /// Where the next field starts, if there is one.
+    at: Option<usize>,
+}
+
+impl<'l> CsvFields<'l> {
+    fn new(line: &'l str, delimiter: u8) -> CsvFields<'l> {
+        CsvFields { line, delimiter, at: Some(0) }
+    }
+}
+
+impl<'l> Iterator for CsvFields<'l> {
+    type Item = Located<'l>;
+
+    fn next(&mut self) -> Option<Located<'l>> {
+        let (line, start) = (self.line, self.at?);
+        let bytes = line.as_bytes();
+        let delimiter_from = |at: usize| memchr(self.delimiter, &bytes[at..]).map_or(line.len(), |i| at + i);
+
+        let (value, verbatim, end) = if bytes.get(start) == Some(&b'"') {
+            let mut value = String::new();
+            let mut at = start + 1;
+            let mut escaped = false;
+
+            loop {
+                match line[at..].find('"') {
+                    Some(quote) => {
+                        value.push_str(&line[at..at + quote]);
+                        at += quote + 1;
+                        if bytes.get(at) != Some(&b'"') {
+                            break;
+                        }
+                        value.push('"');
+                        escaped = true;
+                        at += 1;
+                    },
+                    // Unterminated: the rest of the line.
+                    None => {
+                        value.push_str(&line[at..]);
+                        at = line.len();
+                        break;
+                    },
+                }
+            }
+
+            let end = delimiter_from(at);
+            if escaped {
+                (Cow::Owned(value), None, end)
+            } else {
+                (Cow::Borrowed(&line[start + 1..start + 1 + value.len()]), Some(start + 1), end)
+            }
+        } else {
+            let end = delimiter_from(start);
+            (Cow::Borrowed(&line[start..end]), Some(start), end)
+        };
+
+        self.at = if end < line.len() { Some(end + 1) } else { None };
+        Some(Located { start, end, value, verbatim })
+    }
+}
+
+/// The value at `path` in the JSON on `line`. Strings are unescaped; other
+/// values are taken as written. Missing values, `null` and lines that
+/// aren't JSON have nothing to match.
+fn json_field<'l>(line: &'l str, path: &[String]) -> Option<Located<'l>> {
+    let mut raw: &'l RawValue = serde_json::from_str(line).ok()?;
+
+    for key in path {
+        let json: &'l str = raw.get();
+        raw = match json.as_bytes().first() {
+            Some(b'{') => serde_json::from_str::<BTreeMap<String, &'l RawValue>>(json).ok()?.remove(key)?,
+            Some(b'[') => serde_json::from_str::<Vec<&'l RawValue>>(json).ok()?.into_iter().nth(key.parse().ok()?)?,
+            _ => return None,
+        };
+    }
+
+    let json: &'l str = raw.get();
+    let start = json.as_ptr() as usize - line.as_ptr() as usize;
+    let end = start + json.len();
+
+    if json == "null" {
+        return None;
+    }
+    if !json.starts_with('"') {
+        return Some(Located { start, end, value: Cow::Borrowed(json), verbatim: Some(start) });
+    }
+
+    let value: String = serde_json::from_str(json).ok()?;
+    let quoted = &json[1..json.len() - 1];
+
+    Some(if quoted == value {
+        Located { start, end, value: Cow::Borrowed(quoted), verbatim: Some(start + 1) }
+    } else {
+        Located { start, end, value: Cow::Owned(value), verbatim: None }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn field(format: Format, field: &str) -> Field {
+        let config = Config { parse: Some(format), field: Some(field.to_string()), ..Default::default() };
+        Field::new(&config).unwrap()
+    }
+
+    #[test]
+    fn csv_columns() {
+        let line = r#"42,"Smith, John","said ""hi""",smith@example.com"#;
+        let values = |line| -> Vec<String> {
+            CsvFields::new(line, b',').map(|field| field.value.into_owned()).collect()
+        };
+
+        assert_eq!(vec!["42", "Smith, John", r#"said "hi""#, "smith@example.com"], values(line));
+        assert_eq!(vec!["", "a", ""], values(",a,"));
+
+        assert_eq!(Some((4, 9)), field(Format::Csv, "2").find("Smith", line));
+        assert_eq!(None, field(Format::Csv, "4").find("Smith", line));
+        // Unquoting moved the text, so the whole field stands in for the match.
+        assert_eq!(Some((17, 30)), field(Format::Csv, "3").find("\"hi\"", line));
+        assert_eq!(None, field(Format::Csv, "5").find("42", line));
+    }
+
+    #[test]
+    fn csv_column_names_come_from_the_header() {
+        let root = |delimiter| {
+            let user = Field { delimiter, ..field(Format::Csv, "user") };
+            Query::Field(Box::new(Query::Literal("root".to_string())), user)
+        };
+        let mut input = &b"id;user;shell\n1;root;/bin/sh\n2;bob;/bin/root\n"[..];
+
+        let (query, len) = read_header(&root(b';'), Path::new("users.csv"), &mut input).unwrap().unwrap();
+        assert_eq!(14, len);
+        let lines: Vec<String> = input.lines().map(Result::unwrap).collect();
+        assert_eq!(Some((2, 6)), query.find(&lines[0]));
+        assert_eq!(None, query.find(&lines[1]));
+
+        match read_header(&root(b','), Path::new("users.csv"), &mut &b"id;user\n"[..]) {
+            Err(Error::InvalidArgument(message)) => assert_eq!("users.csv: no column named 'user' in the header", message),
+            _ => panic!("the missing column isn't reported"),
+        }
+    }
+
+    #[test]
+    fn json_paths() {
+        let line = r#"{"level":"error","user":{"name":"Zoë \"Z\"","id":7},"tags":["db","slow"],"msg":null}"#;
+
+        assert_eq!(Some((10, 13)), field(Format::Json, "level").find("err", line));
+        assert_eq!(Some((67, 71)), field(Format::Json, "tags.1").find("slow", line));
+        assert_eq!(Some((50, 51)), field(Format::Json, "user.id").find("7", line));
+        assert_eq!(Some((32, 44)), field(Format::Json, "user.name").find("\"Z\"", line));
+        assert_eq!(None, field(Format::Json, "msg").find("null", line));
+        assert_eq!(None, field(Format::Json, "user.email").find("@", line));
+        assert_eq!(None, field(Format::Json, "level").find("error", "not json, error"));
+    }
+}
@@ -0,0 +1,129 @@
+use searcher::Matcher;
+
+/// The part of a line closest to a fuzzy query.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Approximate {
+    /// Byte range of the closest substring.
+    pub start: usize,
+    pub end: usize,
+    /// Levenshtein distance between that substring and the query.
+    pub distance: usize,
+}
+
+/// Finds the substrings of a line that are within `max_distance` single
+/// character insertions, deletions or substitutions of one of the patterns.
+#[derive(Debug, Clone)]
+pub struct Fuzzy {
+    patterns: Vec<Vec<char>>,
+    max_distance: usize,
+    case_sensitive: bool,
+}
+
+impl Fuzzy {
+    pub fn new(patterns: &[String], max_distance: usize, case_sensitive: bool) -> Fuzzy {
+        let patterns = patterns.iter().map(|pattern| pattern.chars().collect()).collect();
+
+        Fuzzy { patterns, max_distance, case_sensitive }
+    }
+
+    /// The closest match in `line` over all patterns, if it is close enough.
+    /// Ties go to the earliest, and then to the longest.
+    pub fn best(&self, line: &str) -> Option<Approximate> {
+        let mut best: Option<Approximate> = None;
+
+        for pattern in &self.patterns {
+            if let Some(found) = self.closest(pattern, line) {
+                if best.is_none_or(|best| (found.distance, found.start) < (best.distance, best.start)) {
+                    best = Some(found);
+                }
+            }
+        }
+
+        best.filter(|best| best.distance <= self.max_distance)
+    }
+
+    /// Sellers' algorithm: edit distance to `pattern` where the match may
+    /// start and end anywhere in `line`, one row of the table at a time.
+    /// Each cell also remembers where in the line its alignment starts.
+    fn closest(&self, pattern: &[char], line: &str) -> Option<Approximate> {
+        let chars: Vec<(usize, char)> = line.char_indices().collect();
+        let offset = |i: usize| chars.get(i).map_or(line.len(), |&(offset, _)| offset);
+
+        if chars.len() + self.max_distance < pattern.len() {
+            return None;
+        }
+
+        // (distance, start) for the pattern prefix so far against the line
+        // up to each column. Any column can start a match for free.
+        let mut row: Vec<(usize, usize)> = (0..=chars.len()).map(|j| (0, j)).collect();
+
+        for (i, &p) in pattern.iter().enumerate() {
+            let mut next = Vec::with_capacity(row.len());
+            next.push((i + 1, 0));
+
+            for (j, &(_, c)) in chars.iter().enumerate() {
+                let (diagonal, start) = row[j];
+                let substitute = (diagonal + if self.same(p, c) { 0 } else { 1 }, start);
+                let skip_pattern = (row[j + 1].0 + 1, row[j + 1].1);
+                let skip_line = (next[j].0 + 1, next[j].1);
+
+                next.push(*[substitute, skip_pattern, skip_line].iter()
+                    .min_by_key(|&&(distance, start)| (distance, usize::MAX - start))
+                    .expect("three candidates"));
+            }
+            row = next;
+        }
+
+        row.iter().enumerate()
+            .min_by_key(|&(end, &(distance, start))| (distance, start, usize::MAX - end))
+            .map(|(end, &(distance, start))| Approximate {
+                start: offset(start),
+                end: offset(end),
+                distance,
+            })
+    }
+
+    fn same(&self, a: char, b: char) -> bool {
+        a == b || !self.case_sensitive && a.to_lowercase().eq(b.to_lowercase())
+    }
+}
+
+impl Matcher for Fuzzy {
+    fn find(&self, line: &str) -> Option<(usize, usize)> {
+        self.best(line).map(|best| (best.start, best.end))
+    }
+
+    /// Only the closest match counts.
+    fn find_all(&self, line: &str) -> Vec<(usize, usize)> {
+        self.find(line).into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fuzzy(pattern: &str, max_distance: usize) -> Fuzzy {
+        Fuzzy::new(&[pattern.to_string()], max_distance, true)
+    }
+
+    #[test]
+    fn finds_misspellings_within_distance() {
+        let line = "login failed for user Jahn Smiht";
+
+        assert_eq!(Some(Approximate { start: 22, end: 26, distance: 1 }), fuzzy("John", 1).best(line));
+        assert_eq!(None, fuzzy("John", 0).best(line));
+        // Dropping the `t` is closer than swapping it back.
+        assert_eq!(Some((27, 31)), fuzzy("Smith", 2).find(line));
+        assert_eq!(Some(0), fuzzy("login", 0).best(line).map(|best| best.distance));
+        assert_eq!(None, fuzzy("Zoe", 1).best(line));
+    }
+
+    #[test]
+    fn case_and_unicode() {
+        let matcher = Fuzzy::new(&["zoë".to_string()], 1, false);
+
+        assert_eq!(Some((4, 8)), matcher.find("hi, ZOË!"));
+        assert_eq!(Some((4, 7)), matcher.find("hi, Zoe!"));
+    }
+}
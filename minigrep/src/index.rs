@@ -0,0 +1,465 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File, Metadata};
+use std::io::{self, prelude::*, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::UNIX_EPOCH;
+
+use rayon::prelude::*;
+
+use config::Config;
+use error::Error;
+use search;
+use walk::{self, WalkOptions};
+
+/// Where in a directory its index is kept.
+pub const INDEX_FILE: &str = ".minigrep-index";
+
+const MAGIC: &[u8] = b"minigrep index 1\n";
+
+/// How much of a file is read at a time while indexing it.
+const BLOCK_LEN: usize = 64 * 1024;
+
+/// A trigram: three bytes, ASCII lowercased, packed into the low 24 bits.
+type Trigram = u32;
+
+/// What the index knows about one file.
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    /// Modification time (seconds and nanoseconds since the epoch) and
+    /// length when the file was indexed, to tell if it changed since.
+    modified: (u64, u32),
+    len: u64,
+    /// The file can't be ruled out by trigrams, e.g. because it is UTF-16.
+    opaque: bool,
+    /// Every trigram in the file, sorted.
+    trigrams: Vec<Trigram>,
+}
+
+impl Entry {
+    fn new(path: &Path, metadata: &Metadata, set: &mut TrigramSet) -> io::Result<Entry> {
+        let mut reader = BufReader::with_capacity(BLOCK_LEN, File::open(path)?);
+
+        // UTF-16 text is transcoded before it is searched, so its bytes say
+        // nothing about what can be found in it.
+        let head = reader.fill_buf()?;
+        let opaque = head.starts_with(&[0xff, 0xfe]) || head.starts_with(&[0xfe, 0xff]);
+
+        Ok(Entry {
+            modified: modified(metadata),
+            len: metadata.len(),
+            opaque,
+            trigrams: if opaque { Vec::new() } else { read_trigrams(reader, set)? },
+        })
+    }
+
+    fn is_fresh(&self, metadata: &Metadata) -> bool {
+        self.modified == modified(metadata) && self.len == metadata.len()
+    }
+}
+
+fn modified(metadata: &Metadata) -> (u64, u32) {
+    metadata.modified().ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or((0, 0), |since| (since.as_secs(), since.subsec_nanos()))
+}
+
+/// Which of the 2^24 possible trigrams have been seen, a bit each (2 MiB),
+/// so indexing a file takes the same memory however big it is. A set is
+/// reused from file to file, and only the words that were set are looked
+/// at and cleared, so small files stay cheap.
+struct TrigramSet {
+    bits: Vec<u64>,
+    /// Indexes of the words in `bits` that aren't zero.
+    used: Vec<usize>,
+}
+
+impl TrigramSet {
+    fn new() -> TrigramSet {
+        TrigramSet { bits: vec![0; (1 << 24) / 64], used: Vec::new() }
+    }
+
+    fn insert(&mut self, t: Trigram) {
+        let word = &mut self.bits[(t >> 6) as usize];
+        if *word == 0 {
+            self.used.push((t >> 6) as usize);
+        }
+        *word |= 1 << (t & 63);
+    }
+
+    /// The trigrams in the set, sorted, leaving it empty.
+    fn take_sorted(&mut self) -> Vec<Trigram> {
+        let mut trigrams = Vec::new();
+
+        self.used.sort_unstable();
+        for &i in &self.used {
+            let mut bits = self.bits[i];
+            while bits != 0 {
+                trigrams.push((i as Trigram) << 6 | bits.trailing_zeros());
+                bits &= bits - 1;
+            }
+            self.bits[i] = 0;
+        }
+        self.used.clear();
+        trigrams
+    }
+}
+
+/// Every distinct trigram of what `reader` holds, sorted, collected in
+/// `set`. It is read a block at a time, the last two bytes of each carried
+/// over to the next.
+fn read_trigrams<R: Read>(mut reader: R, set: &mut TrigramSet) -> io::Result<Vec<Trigram>> {
+    let mut block = vec![0; BLOCK_LEN];
+    let (mut window, mut seen): (Trigram, usize) = (0, 0);
+
+    loop {
+        let len = match reader.read(&mut block) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        for &byte in &block[..len] {
+            window = (window << 8 | byte.to_ascii_lowercase() as Trigram) & 0xff_ffff;
+            seen += 1;
+            if seen >= 3 {
+                set.insert(window);
+            }
+        }
+    }
+
+    Ok(set.take_sorted())
+}
+
+/// Every distinct trigram of `bytes`, sorted.
+fn trigrams(bytes: &[u8]) -> Vec<Trigram> {
+    let mut trigrams: Vec<Trigram> = bytes.windows(3)
+        .map(|t| (t[0].to_ascii_lowercase() as Trigram) << 16 | (t[1].to_ascii_lowercase() as Trigram) << 8
+            | t[2].to_ascii_lowercase() as Trigram)
+        .collect();
+
+    trigrams.sort_unstable();
+    trigrams.dedup();
+    trigrams
+}
+
+/// The trigrams a file has to contain for `config` to find anything in it:
+/// all of those of at least one of the patterns. `None` if files can't be
+/// ruled out this way, because some pattern is too short, the query isn't
+/// a literal or every file is reported whether it matches or not.
+pub fn required_trigrams(config: &Config) -> Option<Vec<Vec<Trigram>>> {
+    if config.regex || config.fuzzy.is_some() || config.field.is_some() || config.invert_match || config.count
+        || config.files_without_match || config.json || config.search_zip || config.encoding.is_some() {
+        return None;
+    }
+    let case_sensitive = search::case_sensitive(config);
+
+    config.patterns.iter()
+        .map(|pattern| {
+            // Case folding can turn `k` and `s` into non-ASCII letters, and
+            // non-ASCII letters aren't folded in the index at all.
+            let usable = |t: &Trigram| case_sensitive || [t >> 16, t >> 8, *t].iter().all(|&b| {
+                let b = (b & 0xff) as u8;
+                b.is_ascii() && b != b'k' && b != b's'
+            });
+            let required: Vec<Trigram> = trigrams(pattern.as_bytes()).into_iter().filter(usable).collect();
+
+            if required.is_empty() { None } else { Some(required) }
+        })
+        .collect()
+}
+
+/// A trigram index of the files below a directory.
+#[derive(Debug, Default, PartialEq)]
+pub struct Index {
+    /// By path relative to the directory.
+    files: BTreeMap<PathBuf, Entry>,
+}
+
+/// What `Index::update` did.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Updated {
+    pub files: usize,
+    /// Files read because they are new or changed.
+    pub read: usize,
+    pub removed: usize,
+}
+
+impl Index {
+    /// The index kept in `root`, if there is one.
+    pub fn load(root: &Path) -> Result<Option<Index>, Error> {
+        let path = root.join(INDEX_FILE);
+
+        match File::open(&path) {
+            Ok(file) => Index::read(BufReader::new(file))
+                .map(Some)
+                .map_err(|e| Error::reading(path, e)),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(Error::reading(path, e)),
+        }
+    }
+
+    /// Index every file below `root` that `options` lets through, reusing
+    /// what `previous` knows about files that haven't changed since, and
+    /// save the result in `root`.
+    pub fn update(root: &Path, options: &WalkOptions, previous: Option<Index>) -> Result<(Index, Updated), Error> {
+        let previous = previous.unwrap_or_default();
+        let files = walk::files(root, options)?;
+
+        // One trigram set per worker, rather than 2 MiB per file.
+        let entries: Vec<(PathBuf, Entry, bool)> = files.par_iter()
+            .map_init(TrigramSet::new, |set, path| {
+                let relative = path.strip_prefix(root).unwrap_or(path);
+                if relative == Path::new(INDEX_FILE) {
+                    return None;
+                }
+
+                let entry = fs::metadata(path).and_then(|metadata| {
+                    match previous.files.get(relative) {
+                        Some(entry) if entry.is_fresh(&metadata) => Ok((entry.clone(), false)),
+                        _ => Entry::new(path, &metadata, set).map(|entry| (entry, true)),
+                    }
+                });
+
+                match entry {
+                    Ok((entry, read)) => Some((relative.to_path_buf(), entry, read)),
+                    Err(e) => {
+                        eprintln!("minigrep: {}: {}", path.display(), e);
+                        None
+                    },
+                }
+            })
+            .flatten()
+            .collect();
+
+        let mut index = Index::default();
+        let mut updated = Updated::default();
+
+        for (path, entry, read) in entries {
+            updated.files += 1;
+            if read {
+                updated.read += 1;
+            }
+            index.files.insert(path, entry);
+        }
+        updated.removed = previous.files.keys().filter(|path| !index.files.contains_key(*path)).count();
+
+        let path = root.join(INDEX_FILE);
+        index.save(&path).map_err(|source| Error::Io { path: Some(path), source })?;
+
+        Ok((index, updated))
+    }
+
+    /// Whether `path`, a file below the indexed directory, could contain
+    /// one of the `required` sets of trigrams. Files that changed since
+    /// they were indexed, or that aren't in the index, always could.
+    pub fn may_match(&self, root: &Path, path: &Path, required: &[Vec<Trigram>]) -> bool {
+        let entry = match path.strip_prefix(root).ok().and_then(|relative| self.files.get(relative)) {
+            Some(entry) => entry,
+            None => return true,
+        };
+
+        match fs::metadata(path) {
+            Ok(ref metadata) if entry.is_fresh(metadata) && !entry.opaque => {
+                required.iter().any(|all| all.iter().all(|t| entry.trigrams.binary_search(t).is_ok()))
+            },
+            _ => true,
+        }
+    }
+
+    /// Write the index to `path`, replacing it only once it is complete.
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let temp = path.with_file_name(format!("{}.{}", INDEX_FILE, process::id()));
+        let result = File::create(&temp).and_then(|file| {
+            let mut out = BufWriter::new(file);
+            self.write(&mut out)?;
+            out.into_inner().map_err(|e| e.into_error())?.sync_all()
+        });
+
+        match result {
+            Ok(()) => fs::rename(&temp, path),
+            Err(e) => {
+                let _ = fs::remove_file(&temp);
+                Err(e)
+            },
+        }
+    }
+
+    /// The file format: `MAGIC`, the number of files, then for each its
+    /// path, modification time, length, whether it is opaque and its
+    /// trigrams as deltas from the previous one. Numbers are LEB128.
+    fn write<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        write_number(out, self.files.len() as u64)?;
+
+        for (path, entry) in &self.files {
+            let path = path.to_string_lossy();
+            write_number(out, path.len() as u64)?;
+            out.write_all(path.as_bytes())?;
+            write_number(out, entry.modified.0)?;
+            write_number(out, entry.modified.1 as u64)?;
+            write_number(out, entry.len)?;
+            write_number(out, entry.opaque as u64)?;
+            write_number(out, entry.trigrams.len() as u64)?;
+
+            let mut last = 0;
+            for &t in &entry.trigrams {
+                write_number(out, (t - last) as u64)?;
+                last = t;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read<R: BufRead>(input: R) -> io::Result<Index> {
+        // A file that ends early was cut off, or isn't an index at all.
+        Index::read_files(input).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => invalid(),
+            _ => e,
+        })
+    }
+
+    fn read_files<R: BufRead>(mut input: R) -> io::Result<Index> {
+        let mut magic = vec![0; MAGIC.len()];
+        input.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(invalid());
+        }
+
+        let mut index = Index::default();
+
+        for _ in 0..read_number(&mut input)? {
+            let len = read_number(&mut input)?;
+            let mut path = Vec::new();
+            if input.by_ref().take(len).read_to_end(&mut path)? as u64 != len {
+                return Err(invalid());
+            }
+            let path = String::from_utf8(path).map_err(|_| invalid())?;
+
+            let modified = (read_number(&mut input)?, read_number(&mut input)? as u32);
+            let len = read_number(&mut input)?;
+            let opaque = read_number(&mut input)? != 0;
+            let count = read_number(&mut input)? as usize;
+
+            let mut trigrams = Vec::with_capacity(count.min(1 << 24));
+            let mut last: u64 = 0;
+            for _ in 0..count {
+                last = last.checked_add(read_number(&mut input)?).filter(|&t| t < 1 << 24).ok_or_else(invalid)?;
+                trigrams.push(last as Trigram);
+            }
+
+            index.files.insert(PathBuf::from(path), Entry { modified, len, opaque, trigrams });
+        }
+
+        Ok(index)
+    }
+}
+
+fn invalid() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "not a minigrep index, or a damaged one")
+}
+
+fn write_number<W: Write>(out: &mut W, mut n: u64) -> io::Result<()> {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            return out.write_all(&[byte]);
+        }
+        out.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_number<R: Read>(input: &mut R) -> io::Result<u64> {
+    let mut n = 0;
+    let mut byte = [0];
+
+    for shift in (0..64).step_by(7) {
+        input.read_exact(&mut byte)?;
+        n |= ((byte[0] & 0x7f) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+
+    Err(io::Error::new(io::ErrorKind::InvalidData, "number too long in minigrep index"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+
+    fn config(patterns: &[&str]) -> Config {
+        Config { patterns: patterns.iter().map(|p| p.to_string()).collect(), ..Default::default() }
+    }
+
+    #[test]
+    fn narrows_files_and_updates_incrementally() {
+        let root = env::temp_dir().join(format!("minigrep-index-{}", process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() { run(); }").unwrap();
+        fs::write(root.join("src/lib.rs"), "pub fn search() {}").unwrap();
+        fs::write(root.join("notes.txt"), "Frogs croak.").unwrap();
+
+        let (index, updated) = Index::update(&root, &WalkOptions::default(), None).unwrap();
+        assert_eq!(Updated { files: 3, read: 3, removed: 0 }, updated);
+        assert_eq!(Some(&index), Index::load(&root).unwrap().as_ref());
+
+        let candidates = |index: &Index, config: &Config| -> Vec<&str> {
+            let required = required_trigrams(config).unwrap();
+            ["notes.txt", "src/lib.rs", "src/main.rs"].iter().cloned()
+                .filter(|path| index.may_match(&root, &root.join(path), &required))
+                .collect()
+        };
+
+        assert_eq!(vec!["src/lib.rs"], candidates(&index, &config(&["search"])));
+        assert_eq!(vec!["notes.txt", "src/main.rs"], candidates(&index, &Config { case_sensitive: false, ..config(&["FROG", "run()"]) }));
+        assert!(required_trigrams(&config(&["fn", "search"])).is_none());
+
+        fs::write(root.join("src/lib.rs"), "pub fn frobnicate() {}").unwrap();
+        fs::remove_file(root.join("notes.txt")).unwrap();
+
+        let (index, updated) = Index::update(&root, &WalkOptions::default(), Some(index)).unwrap();
+        assert_eq!(Updated { files: 2, read: 1, removed: 1 }, updated);
+        // Files the index doesn't know are never ruled out.
+        assert_eq!(vec!["notes.txt", "src/lib.rs"], candidates(&index, &config(&["frobnicate"])));
+
+        fs::write(root.join(INDEX_FILE), &MAGIC[..5]).unwrap();
+        let damaged = Index::load(&root).unwrap_err();
+        assert!(::error::report(&damaged).ends_with("not a minigrep index, or a damaged one"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn trigrams_span_blocks() {
+        let split = (&b"xAb"[..]).chain(&b"Cd"[..]).chain(&b"x"[..]);
+        let packed = |t: &[u8]| (t[0] as Trigram) << 16 | (t[1] as Trigram) << 8 | t[2] as Trigram;
+
+        let mut expected: Vec<Trigram> = [b"xab", b"abc", b"bcd", b"cdx"].iter().map(|t| packed(&t[..])).collect();
+        expected.sort();
+        let mut set = TrigramSet::new();
+        assert_eq!(expected, read_trigrams(split, &mut set).unwrap());
+        assert_eq!(expected, trigrams(b"xAbCdx"));
+        assert!(trigrams(b"ab").is_empty());
+
+        // The set is left empty for the next file.
+        assert_eq!(vec![packed(b"abc")], read_trigrams(&b"abc"[..], &mut set).unwrap());
+        assert!(set.used.is_empty() && set.bits.iter().all(|&word| word == 0));
+    }
+
+    #[test]
+    fn numbers_round_trip() {
+        let mut out = Vec::new();
+        for &n in &[0, 127, 128, 300, u64::MAX] {
+            write_number(&mut out, n).unwrap();
+        }
+
+        let mut input = &out[..];
+        let read: Vec<u64> = (0..5).map(|_| read_number(&mut input).unwrap()).collect();
+        assert_eq!(vec![0, 127, 128, 300, u64::MAX], read);
+    }
+}
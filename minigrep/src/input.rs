@@ -0,0 +1,145 @@
+use std::fs::File;
+use std::io::{self, prelude::*, BufReader};
+use std::path::Path;
+
+use bzip2::read::MultiBzDecoder;
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use flate2::read::MultiGzDecoder;
+use xz2::read::XzDecoder;
+
+use config::Config;
+
+/// How many leading bytes are inspected when deciding if a file is binary.
+const BINARY_SNIFF_LEN: usize = 8 * 1024;
+
+/// The path naming standard input.
+pub const STDIN: &str = "-";
+
+/// How standard input is named in output.
+pub const STDIN_LABEL: &str = "(standard input)";
+
+/// Open `path` for searching; `-` is standard input.
+///
+/// With `config.search_zip`, gzip, bzip2 and xz data is recognized by its
+/// magic bytes, whatever the file is called, and decompressed as it is read.
+/// Concatenated streams, as left behind by some log rotators, are read
+/// through to the end.
+///
+/// The text is then transcoded to UTF-8: from `config.encoding` if one was
+/// given, otherwise according to its byte order mark if it starts with one.
+/// Anything else is passed through as is.
+pub fn open(path: &Path, config: &Config) -> io::Result<Box<dyn BufRead>> {
+    let mut reader: Box<dyn BufRead> = if path == Path::new(STDIN) {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(BufReader::new(File::open(path)?))
+    };
+
+    if config.search_zip {
+        reader = decompressed(reader)?;
+    }
+
+    if config.encoding.is_none() && !has_bom(reader.fill_buf()?) {
+        return Ok(reader);
+    }
+
+    let decoder = DecodeReaderBytesBuilder::new()
+        .encoding(config.encoding)
+        .build(reader);
+
+    Ok(Box::new(BufReader::new(decoder)))
+}
+
+fn has_bom(head: &[u8]) -> bool {
+    head.starts_with(&[0xef, 0xbb, 0xbf])
+        || head.starts_with(&[0xff, 0xfe])
+        || head.starts_with(&[0xfe, 0xff])
+}
+
+/// Input is treated as binary when a NUL byte shows up near its start.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.iter().take(BINARY_SNIFF_LEN).any(|&b| b == 0)
+}
+
+/// The name of `path` in output.
+pub fn label(path: &Path) -> &Path {
+    if path == Path::new(STDIN) {
+        Path::new(STDIN_LABEL)
+    } else {
+        path
+    }
+}
+
+fn decompressed<'a>(mut reader: Box<dyn BufRead + 'a>) -> io::Result<Box<dyn BufRead + 'a>> {
+    let (gzip, bzip2, xz) = {
+        let head = reader.fill_buf()?;
+
+        (
+            head.starts_with(&[0x1f, 0x8b]),
+            head.starts_with(b"BZh"),
+            head.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]),
+        )
+    };
+
+    Ok(if gzip {
+        Box::new(BufReader::new(MultiGzDecoder::new(reader)))
+    } else if bzip2 {
+        Box::new(BufReader::new(MultiBzDecoder::new(reader)))
+    } else if xz {
+        Box::new(BufReader::new(XzDecoder::new_multi_decoder(reader)))
+    } else {
+        reader
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{env, fs, process};
+
+    use encoding_rs::Encoding;
+    use flate2::{write::GzEncoder, Compression};
+
+    #[test]
+    fn detects_binary() {
+        assert!(is_binary(b"ELF\0\x01\x02"));
+        assert!(!is_binary("Rust:\nsafe, fast, productive.".as_bytes()));
+    }
+
+    #[test]
+    fn transcodes_utf16_and_latin1() {
+        let path = env::temp_dir().join(format!("minigrep-input-{}", process::id()));
+        let read = |bytes: &[u8], config: &Config| {
+            fs::write(&path, bytes).unwrap();
+            let mut contents = String::new();
+            open(&path, config).unwrap().read_to_string(&mut contents).unwrap();
+            contents
+        };
+
+        let utf16: Vec<u8> = [0xfeff_u16].iter().cloned()
+            .chain("Trust me.".encode_utf16())
+            .flat_map(|unit| unit.to_le_bytes().to_vec())
+            .collect();
+        assert_eq!("Trust me.", read(&utf16, &Config::default()));
+
+        let latin1 = Config { encoding: Encoding::for_label(b"latin1"), ..Default::default() };
+        assert_eq!("Caf\u{e9}", read(b"Caf\xe9", &latin1));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn sniffs_gzip() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"Rust:\nsafe, fast, productive.\n").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut contents = String::new();
+        decompressed(Box::new(&compressed[..])).unwrap().read_to_string(&mut contents).unwrap();
+        assert_eq!("Rust:\nsafe, fast, productive.\n", contents);
+
+        let mut plain = String::new();
+        decompressed(Box::new(&b"Pick three."[..])).unwrap().read_to_string(&mut plain).unwrap();
+        assert_eq!("Pick three.", plain);
+    }
+}
@@ -1,60 +1,405 @@
-use std::{env, error::Error, fs::File};
+extern crate aho_corasick;
+extern crate bzip2;
+extern crate encoding_rs;
+extern crate encoding_rs_io;
+extern crate flate2;
+extern crate ignore;
+extern crate memchr;
+extern crate rayon;
+extern crate regex;
+extern crate serde_json;
+extern crate xz2;
+
+use std::{io, thread};
+use std::cell::Cell;
+use std::collections::BTreeMap;
 use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
 
-#[derive(Debug)]
-pub struct Config {
-    pub query: String,
-    pub filename: String,
-    pub case_sensitive: bool,
-}
+use rayon::prelude::*;
 
-impl Config {
-    pub fn new(mut args: env::Args) -> Result<Config, &'static str> {
-        args.next();
+use index::Index;
+use printer::{Printer, Shifted, Stats};
 
-        let query = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get a query string"),
-        };
+pub use config::{with_config_file, ColorChoice, Command, Config, Sort, USAGE, VERSION};
+pub use printer::Colors;
+pub use error::{report, Error};
+pub use walk::WalkOptions;
+pub use search::{search, search_case_insensitive, search_regex, search_reader};
+pub use search::{search_whole_lines, search_whole_words};
+pub use search::{Context, Event, Match, Query};
+pub use searcher::{Matcher, Searcher, Sink};
+
+mod config;
+mod error;
+mod field;
+mod fuzzy;
+mod index;
+mod input;
+mod printer;
+mod replace;
+mod search;
+mod searcher;
+mod walk;
+mod watch;
+
+/// How a search went, with the exit status grep would use for it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    /// Something was selected: a line, or a file for `-L`.
+    Matched = 0,
+    NoMatch = 1,
+    /// Some inputs couldn't be searched; the problems were reported on stderr.
+    Error = 2,
+}
 
-        let filename = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get a file name"),
+impl Status {
+    fn new(config: &Config, total: &Stats) -> Status {
+        let selected = if config.files_without_match {
+            total.files_searched > total.files_with_matches
+        } else {
+            total.matched_lines > 0
         };
 
-        let case_sensitive = env::var("CASE_INSENSITIVE").is_err();
+        if total.errors > 0 {
+            Status::Error
+        } else if selected {
+            Status::Matched
+        } else {
+            Status::NoMatch
+        }
+    }
+}
+
+pub fn run(mut config: Config) -> Result<Status, Error> {
+    config.color = config.color.resolve();
+
+    let query = Query::new(&config)?;
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    let result = match config.paths.as_slice() {
+        _ if config.watch => watch::watch(&query, &config, &mut out).map(|()| Stats::default()),
+        [ref path] if !Path::new(path).is_dir() && !config.in_place => {
+            // A lone file streams straight to stdout.
+            let path = Path::new(path);
+            let mut reader = input::open(path, &config).map_err(|e| Error::reading(path, e))?;
+            let header = field::read_header(&query, path, &mut reader)?;
+            let query = header.as_ref().map_or(&query, |header| &header.0);
+            let mut printer = past_header(printer::for_config(&config, query, &mut out, false), &header);
+
+            search_file(query, &config, reader, input::label(path), &mut *printer)
+                .map_err(|failed| failed.into_error(path))
+        },
+        _ => {
+            let files = collect_files(&config)?;
+            search_parallel(&query, &config, &files, &mut out).map_err(Error::from)
+        },
+    };
+
+    let result = result.and_then(|total| {
+        if config.json {
+            printer::json_summary(&mut out, &total)?;
+        }
+        Ok(Status::new(&config, &total))
+    });
+
+    match result {
+        // The reader went away (e.g. `minigrep ... | head`) after being
+        // given some output, nothing left to do.
+        Err(Error::Io { ref source, .. }) if source.kind() == io::ErrorKind::BrokenPipe => {
+            Ok(Status::Matched)
+        },
+        result => result,
+    }
+}
+
+/// Print every file type `--type` accepts, as `name: glob, glob...` lines.
+pub fn list_types(options: &WalkOptions) -> Result<(), Error> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    for def in options.file_types()?.definitions() {
+        if !output_open(writeln!(out, "{}: {}", def.name(), def.globs().join(", ")))? {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Build or update the index of each directory in `dirs`, reporting what
+/// changed.
+pub fn index(dirs: &[String], options: &WalkOptions) -> Result<(), Error> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut reporting = true;
+
+    for dir in dirs {
+        let dir = Path::new(dir);
+        // A damaged index is built again from scratch.
+        let previous = Index::load(dir).unwrap_or_else(|e| {
+            eprintln!("minigrep: {}", report(&e));
+            None
+        });
+        let (_, updated) = Index::update(dir, options, previous)?;
+
+        // The rest are still indexed once nobody reads the report.
+        if reporting {
+            reporting = output_open(writeln!(out, "{}: {} files indexed, {} read, {} removed",
+                                             dir.display(), updated.files, updated.read, updated.removed))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether output can still be written after `written`: not if the reader
+/// went away (e.g. `minigrep --type-list | head`), which isn't an error.
+fn output_open(written: io::Result<()>) -> Result<bool, Error> {
+    match written {
+        Ok(()) => Ok(true),
+        Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Every file named by `config.paths`, with directories walked. Files
+/// found by walking are marked `true`; only they are subject to the globs
+/// and file types of `config.walk`, and with `--use-index` to the index
+/// of the directory, if it has one.
+fn collect_files(config: &Config) -> Result<Vec<(PathBuf, bool)>, Error> {
+    let mut files = Vec::new();
+    let required = if config.use_index { index::required_trigrams(config) } else { None };
 
-        Ok(Config { query, filename, case_sensitive })
-    }}
+    for path in &config.paths {
+        if Path::new(path).is_dir() {
+            let mut walked = walk::files(path, &config.walk)?;
 
-pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    contents.lines()
-        .filter(|line| line.contains(query))
-        .collect()
+            if let Some(ref required) = required {
+                match Index::load(Path::new(path)) {
+                    Ok(Some(index)) => walked.retain(|file| index.may_match(Path::new(path), file, required)),
+                    Ok(None) => {},
+                    // Every file is searched, as if there were no index.
+                    Err(e) => eprintln!("minigrep: {}", report(&e)),
+                }
+            }
+            files.extend(walked.into_iter().map(|file| (file, true)));
+        } else {
+            files.push((PathBuf::from(path), false));
+        }
+    }
+
+    if config.sort == Sort::Path {
+        files.sort();
+    }
+
+    Ok(files)
 }
 
-pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    let query = query.to_lowercase();
+/// Search all files on a work-stealing thread pool. Each file is searched
+/// into its own buffer, so its output is written out in one piece; with
+/// `Sort::Path` buffers are also written in path order.
+fn search_parallel<W: Write>(query: &Query, config: &Config, files: &[(PathBuf, bool)],
+                             out: &mut W) -> io::Result<Stats> {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(config.threads)
+        .build()
+        .map_err(io::Error::other)?;
+    let quit = AtomicBool::new(false);
+    let (tx, rx) = mpsc::channel();
+
+    let quit = &quit;
+
+    thread::scope(|scope| {
+        scope.spawn(move || pool.install(|| {
+            files.par_iter().enumerate().for_each_with(tx, |tx, (i, &(ref path, walked))| {
+                if quit.load(Ordering::Relaxed) {
+                    return;
+                }
+
+                let mut buf = Vec::new();
+                let stats = search_path(query, config, path, walked, &mut buf);
+                let _ = tx.send((i, buf, stats));
+            })
+        }));
 
-    contents.lines()
-        .filter(|line| line.to_lowercase().contains(&query))
-        .collect()
+        let mut total = Stats::default();
+        let mut pending = BTreeMap::new();
+        let mut next = 0;
+
+        for (i, buf, stats) in rx {
+            pending.insert(i, (buf, stats));
+
+            // Unsorted output is written as soon as it arrives, sorted output
+            // once everything before it has been written.
+            loop {
+                let ready = match config.sort {
+                    Sort::None => pending.pop_first().map(|(_, ready)| ready),
+                    Sort::Path => pending.remove(&next),
+                };
+                let (buf, stats) = match ready {
+                    Some(ready) => ready,
+                    None => break,
+                };
+
+                if let Err(e) = out.write_all(&buf) {
+                    quit.store(true, Ordering::Relaxed);
+                    return Err(e);
+                }
+                total.add(stats);
+                next += 1;
+            }
+        }
+
+        Ok(total)
+    })
 }
 
-pub fn run(config: Config) -> Result<(), Box<Error>> {
-    let mut f = File::open(config.filename)?;
+/// Search one file of a multi-file search, printing into `out`. Problems
+/// are reported on stderr so the other files still get searched. Binary
+/// files found by walking a directory are skipped. With `--in-place` the
+/// file is rewritten instead.
+fn search_path(query: &Query, config: &Config, path: &Path, walked: bool,
+               out: &mut Vec<u8>) -> Stats {
+    if config.in_place {
+        return replace::rewrite(query, config, path).unwrap_or_else(|e| {
+            eprintln!("minigrep: {}", report(&e));
+            Stats::error()
+        });
+    }
+
+    let mut reader = match input::open(path, config) {
+        Ok(reader) => reader,
+        Err(e) => {
+            eprintln!("minigrep: {}", report(&Error::reading(path, e)));
+            return Stats::error();
+        },
+    };
+
+    let path = input::label(path);
+
+    match reader.fill_buf() {
+        Ok(buf) if walked && !config.text && input::is_binary(buf) => return Stats::default(),
+        Ok(_) => {},
+        Err(e) => {
+            eprintln!("minigrep: {}", report(&Error::reading(path, e)));
+            return Stats::error();
+        },
+    }
 
-    let mut contents = String::new();
-    f.read_to_string(&mut contents)?;
+    let header = match field::read_header(query, path, &mut reader) {
+        Ok(header) => header,
+        Err(e) => {
+            eprintln!("minigrep: {}", report(&e));
+            return Stats::error();
+        },
+    };
+    let query = header.as_ref().map_or(query, |header| &header.0);
+    let mut printer = past_header(printer::for_config(config, query, out, true), &header);
 
-    let results = if config.case_sensitive {
-        search(&config.query, &contents)
+    search_file(query, config, reader, path, &mut *printer).unwrap_or_else(|failed| {
+        eprintln!("minigrep: {}", report(&failed.into_error(path)));
+        Stats::error()
+    })
+}
+
+/// `printer`, numbering lines from after the CSV header `read_header`
+/// read, if it read one.
+fn past_header<'a>(printer: Box<dyn Printer + 'a>, header: &Option<(Query, usize)>) -> Box<dyn Printer + 'a> {
+    match *header {
+        Some((_, len)) => Box::new(Shifted::new(printer, 1, len)),
+        None => printer,
+    }
+}
+
+/// Why searching an input failed: something went wrong reading it, or
+/// writing what was found in it.
+#[derive(Debug)]
+enum Failed {
+    Reading(io::Error),
+    Writing(io::Error),
+}
+
+impl Failed {
+    /// The error to report, which only names the input at `path` if the
+    /// input is to blame.
+    fn into_error(self, path: &Path) -> Error {
+        match self {
+            Failed::Reading(e) => Error::reading(path, e),
+            Failed::Writing(e) => Error::from(e),
+        }
+    }
+}
+
+/// `searcher.search_reader`, telling errors reading `reader` apart from
+/// those `sink` ran into passing the events on.
+fn search_to<R: BufRead, S: Sink + ?Sized>(searcher: &Searcher, query: &Query, reader: R,
+                                           sink: &mut S) -> Result<(), Failed> {
+    let written = Cell::new(true);
+    let result = searcher.search_reader(query, reader, &mut |event: &Event| {
+        sink.event(event).inspect_err(|_| written.set(false))
+    });
+
+    match result {
+        Ok(()) => Ok(()),
+        Err(e) if written.get() => Err(Failed::Reading(e)),
+        Err(e) => Err(Failed::Writing(e)),
+    }
+}
+
+/// Search one input, handing what is found to `printer`.
+fn search_file<R: BufRead>(query: &Query, config: &Config, mut reader: R, path: &Path,
+                           printer: &mut dyn Printer) -> Result<Stats, Failed> {
+    let (before, after) = if config.count || config.files_with_matches || config.files_without_match {
+        (0, 0)
+    } else {
+        (config.before_context, config.after_context)
+    };
+    let searcher = Searcher::new()
+        .before_context(before)
+        .after_context(after)
+        .invert_match(config.invert_match)
+        .max_count(config.max_count)
+        .multiline(config.multiline);
+
+    let binary = !config.text && input::is_binary(reader.fill_buf().map_err(Failed::Reading)?);
+    printer.begin(path, binary).map_err(Failed::Writing)?;
+
+    let result = if query.is_fuzzy() && before == 0 && after == 0 && !config.invert_match {
+        search_ranked(query, &searcher, reader, path, printer)
     } else {
-        search_case_insensitive(&config.query, &contents)
+        search_to(&searcher, query, reader, &mut |event: &Event| printer.event(path, event))
     };
 
-    for line in results {
-        println!("{}", line);
+    let stats = printer.end(path).map_err(Failed::Writing)?;
+    result.map(|()| stats)
+}
+
+/// Search for the fuzzy `query` and hand the matches to `printer` closest
+/// first, then in input order. They are held in memory until the input is
+/// done.
+fn search_ranked<R: BufRead>(query: &Query, searcher: &Searcher, reader: R, path: &Path,
+                             printer: &mut dyn Printer) -> Result<(), Failed> {
+    let mut found = Vec::new();
+
+    search_to(searcher, query, reader, &mut |event: &Event| {
+        if let Event::Match(ref m) = *event {
+            let distance = query.distance(m.line).unwrap_or(0);
+            found.push((distance, m.line_number, m.byte_offset, m.column, m.line.to_string()));
+        }
+        Ok(true)
+    })?;
+
+    found.sort_by_key(|&(distance, line_number, ..)| (distance, line_number));
+
+    for (_, line_number, byte_offset, column, line) in found {
+        let m = Match { line_number, byte_offset, column, line: &line, spans: None };
+        if !printer.event(path, &Event::Match(m)).map_err(Failed::Writing)? {
+            break;
+        }
     }
 
     Ok(())
@@ -64,33 +409,114 @@ pub fn run(config: Config) -> Result<(), Box<Error>> {
 mod test {
     use super::*;
 
+    fn output(args: &[&str], contents: &str) -> (String, Stats) {
+        let args = args.iter().map(|arg| arg.to_string());
+        let config = match Command::parse(args).unwrap() {
+            Command::Search(config) => config,
+            _ => unreachable!(),
+        };
+        let query = Query::new(&config).unwrap();
+        let mut out = Vec::new();
+
+        let stats = {
+            let mut printer = printer::for_config(&config, &query, &mut out, false);
+            search_file(&query, &config, contents.as_bytes(), Path::new("-"), &mut *printer).unwrap()
+        };
+
+        (String::from_utf8(out).unwrap(), stats)
+    }
+
     #[test]
-    fn case_sensitive() {
-        let query = "duct";
-        let contents = "\
-Rust:
-safe, fast, productive.
-Pick three.
-Duct tape.";
+    fn max_count_keeps_trailing_context() {
+        let contents = "a1\nb\na2\nc\na3\n";
+
+        assert_eq!("a1\nb\na2\nc\n", output(&["minigrep", "-m2", "-A1", "a", "-"], contents).0);
+        assert_eq!("2\n", output(&["minigrep", "-cm2", "a", "-"], contents).0);
+        assert_eq!("b\nc\n", output(&["minigrep", "-v", "a", "-"], contents).0);
+    }
+
+    #[test]
+    fn binary_input() {
+        let contents = "ELF\0\nmain\n";
+
+        assert_eq!("Binary file - matches\n", output(&["minigrep", "main", "-"], contents).0);
+        assert_eq!("main\n", output(&["minigrep", "-a", "main", "-"], contents).0);
+        assert_eq!("1\n", output(&["minigrep", "-c", "main", "-"], contents).0);
+    }
+
+    #[test]
+    fn replace_prints_rewritten_lines() {
+        let contents = "Are you nobody, too?\nThen there's a pair of us\n";
 
         assert_eq!(
-            vec!["safe, fast, productive."],
-            search(query, contents),
+            "Are you somebody, too?\n",
+            output(&["minigrep", "-r", "somebody", "nobody", "-"], contents).0,
+        );
+        assert_eq!(
+            "2:Then there's a couple of us\n",
+            output(&["minigrep", "-nE", "-r", "a couple", "a (pair)", "-"], contents).0,
+        );
+        assert_eq!(
+            "Then there's [pair] of us\n",
+            output(&["minigrep", "-E", "--replace=[$1]", r"a (\w+)", "-"], contents).0,
         );
     }
 
     #[test]
-    fn case_insensitive() {
-        let query = "rUsT";
-        let contents = "\
-Rust:
-safe, fast, productive.
-Pick three.
-Trust me.";
+    fn fuzzy_matches_closest_first() {
+        let contents = "login by Jhn Smith\nlogout by John Smith\nlogin by Jane Doe\nlogin by Jon Smith\n";
+
+        assert_eq!(
+            "2:logout by John Smith\n1:login by Jhn Smith\n4:login by Jon Smith\n",
+            output(&["minigrep", "-n", "--fuzzy", "1", "John", "-"], contents).0,
+        );
+        assert_eq!("3\n", output(&["minigrep", "-c", "--fuzzy=1", "John", "-"], contents).0);
 
+        // Ranked by the field, not the whole line.
         assert_eq!(
-            vec!["Rust:", "Trust me."],
-            search_case_insensitive(query, contents),
+            "2:x,John\n1:John,Jhn\n",
+            output(&["minigrep", "-n", "--fuzzy=1", "--parse=csv", "--field=2", "John", "-"], "John,Jhn\nx,John\n").0,
         );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn exit_status() {
+        let config = Config { files_without_match: true, ..Default::default() };
+        let (_, stats) = output(&["minigrep", "-l", "zzz", "-"], "a\n");
+
+        assert_eq!(Status::NoMatch, Status::new(&Config::default(), &stats));
+        assert_eq!(Status::Matched, Status::new(&config, &stats));
+        assert_eq!(Status::Error, Status::new(&config, &Stats::error()));
+    }
+
+    /// Output that can't be written to, as if it were full.
+    struct Full;
+
+    impl Write for Full {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::InvalidData, "full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn output_errors_dont_blame_the_input() {
+        // Ranked fuzzy matches are written after the input is read.
+        for &fuzzy in &[None, Some(0)] {
+            let config = Config { patterns: vec!["a".to_string()], fuzzy, ..Default::default() };
+            let query = Query::new(&config).unwrap();
+            let mut out = Full;
+            let mut printer = printer::for_config(&config, &query, &mut out, false);
+
+            match search_file(&query, &config, &b"a\n"[..], Path::new("a.txt"), &mut *printer) {
+                Err(failed @ Failed::Writing(_)) => {
+                    assert_eq!("I/O error: full", report(&failed.into_error(Path::new("a.txt"))));
+                },
+                other => panic!("expected a write error, got {:?}", other),
+            }
+        }
+    }
+}
@@ -1,16 +1,45 @@
 extern crate minigrep;
 use std::{env, process};
 
-use minigrep::Config;
+use minigrep::Command;
 
 fn main() {
-    let config = Config::new(env::args()).unwrap_or_else(|err| {
-        eprintln!("Problem parsing arguments: {}", err);
-        process::exit(1);
-    });
-
-    if let Err(e) = minigrep::run(config) {
-        eprintln!("Application error: {}", e);
-        process::exit(1);
+    let config = match minigrep::with_config_file(env::args()).and_then(Command::parse) {
+        Ok(Command::Search(config)) => config,
+        Ok(Command::Help) => {
+            print!("{}", minigrep::USAGE);
+            return;
+        },
+        Ok(Command::Version) => {
+            println!("{}", minigrep::VERSION);
+            return;
+        },
+        Ok(Command::TypeList(options)) => {
+            if let Err(e) = minigrep::list_types(&options) {
+                eprintln!("Application error: {}", minigrep::report(&e));
+                process::exit(2);
+            }
+            return;
+        },
+        Ok(Command::Index(dirs, options)) => {
+            if let Err(e) = minigrep::index(&dirs, &options) {
+                eprintln!("Application error: {}", minigrep::report(&e));
+                process::exit(2);
+            }
+            return;
+        },
+        Err(err) => {
+            eprintln!("Problem parsing arguments: {}", minigrep::report(&err));
+            eprintln!("Try 'minigrep --help' for more information.");
+            process::exit(2);
+        },
     };
+
+    match minigrep::run(config) {
+        Ok(status) => process::exit(status as i32),
+        Err(e) => {
+            eprintln!("Application error: {}", minigrep::report(&e));
+            process::exit(2);
+        },
+    }
 }
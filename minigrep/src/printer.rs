@@ -0,0 +1,475 @@
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use config::{ColorChoice, Config};
+use error::Error;
+use search::{Event, Match, Query};
+use searcher::Matcher;
+
+/// Turns search events into output, one file at a time.
+pub trait Printer {
+    /// Called before the first event of `path`, which is `binary` if it
+    /// looked like binary data that wasn't asked to be treated as text.
+    fn begin(&mut self, path: &Path, binary: bool) -> io::Result<()>;
+
+    /// Called for every event of `path`. Returns whether the search of
+    /// `path` should keep going.
+    fn event(&mut self, path: &Path, event: &Event) -> io::Result<bool>;
+
+    /// Called once `path` has been searched, even if searching it failed.
+    fn end(&mut self, path: &Path) -> io::Result<Stats>;
+}
+
+/// The printer `config` asks for, writing to `out`.
+pub fn for_config<'a, W>(config: &'a Config, query: &'a Query, out: W,
+                         with_filename: bool) -> Box<dyn Printer + 'a>
+    where W: Write + 'a,
+{
+    if config.json {
+        Box::new(Json::new(out, query))
+    } else {
+        Box::new(Standard::new(out, config, query, with_filename))
+    }
+}
+
+/// Hands events on to another printer numbered as if some input came
+/// before them, e.g. a CSV header that was read separately.
+pub struct Shifted<'a> {
+    printer: Box<dyn Printer + 'a>,
+    lines: usize,
+    bytes: usize,
+}
+
+impl<'a> Shifted<'a> {
+    pub fn new(printer: Box<dyn Printer + 'a>, lines: usize, bytes: usize) -> Shifted<'a> {
+        Shifted { printer, lines, bytes }
+    }
+}
+
+impl<'a> Printer for Shifted<'a> {
+    fn begin(&mut self, path: &Path, binary: bool) -> io::Result<()> {
+        self.printer.begin(path, binary)
+    }
+
+    fn event(&mut self, path: &Path, event: &Event) -> io::Result<bool> {
+        self.printer.event(path, &event.shifted(self.lines, self.bytes))
+    }
+
+    fn end(&mut self, path: &Path) -> io::Result<Stats> {
+        self.printer.end(path)
+    }
+}
+
+/// What was found in one file, or in all of them once added up.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Stats {
+    pub files_searched: usize,
+    pub files_with_matches: usize,
+    pub matched_lines: usize,
+    /// Individual matches within the matched lines. Only counted by
+    /// printers that look for them.
+    pub matches: usize,
+    /// Files that couldn't be searched.
+    pub errors: usize,
+}
+
+impl Stats {
+    pub fn file(matched_lines: usize, matches: usize) -> Stats {
+        Stats {
+            files_searched: 1,
+            files_with_matches: if matched_lines > 0 { 1 } else { 0 },
+            matched_lines,
+            matches,
+            errors: 0,
+        }
+    }
+
+    pub fn error() -> Stats {
+        Stats { errors: 1, ..Stats::default() }
+    }
+
+    pub fn add(&mut self, other: Stats) {
+        self.files_searched += other.files_searched;
+        self.files_with_matches += other.files_with_matches;
+        self.matched_lines += other.matched_lines;
+        self.matches += other.matches;
+        self.errors += other.errors;
+    }
+}
+
+/// SGR parameters (as in `\x1b[...m`) for each highlighted part of a line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Colors {
+    pub path: String,
+    pub line_number: String,
+    pub separator: String,
+    pub matched: String,
+}
+
+impl Default for Colors {
+    /// The same colors grep uses.
+    fn default() -> Colors {
+        Colors {
+            path: "35".to_string(),
+            line_number: "32".to_string(),
+            separator: "36".to_string(),
+            matched: "1;31".to_string(),
+        }
+    }
+}
+
+impl Colors {
+    /// Apply a `--colors` spec such as `match:1;33`: which part to color
+    /// (`path`, `line`, `separator` or `match`), then its SGR parameters.
+    pub fn set(&mut self, spec: &str) -> Result<(), Error> {
+        let invalid = || Error::InvalidArgument(format!(
+            "option '--colors' expects PART:SGR, such as 'match:1;33', got '{}'", spec));
+        let (part, sgr) = spec.split_once(':').ok_or_else(invalid)?;
+
+        if sgr.is_empty() || !sgr.chars().all(|c| c.is_ascii_digit() || c == ';') {
+            return Err(invalid());
+        }
+
+        let color = match part {
+            "path" => &mut self.path,
+            "line" => &mut self.line_number,
+            "separator" => &mut self.separator,
+            "match" => &mut self.matched,
+            _ => return Err(invalid()),
+        };
+        *color = sgr.to_string();
+
+        Ok(())
+    }
+}
+
+/// Byte ranges of the matches on the line of `m`.
+fn matches(query: &Query, m: &Match) -> Vec<(usize, usize)> {
+    match m.spans {
+        Some(spans) => spans.to_vec(),
+        None => query.find_all(m.line),
+    }
+}
+
+/// grep-style output: selected lines with optional prefixes, or a count
+/// or file name per file.
+pub struct Standard<'a, W> {
+    out: W,
+    config: &'a Config,
+    query: &'a Query,
+    colors: Option<Colors>,
+    with_filename: bool,
+    binary: bool,
+    count: usize,
+}
+
+impl<'a, W: Write> Standard<'a, W> {
+    pub fn new(out: W, config: &'a Config, query: &'a Query, with_filename: bool) -> Standard<'a, W> {
+        let colors = if config.color == ColorChoice::Always {
+            Some(config.colors.clone())
+        } else {
+            None
+        };
+
+        Standard { out, config, query, colors, with_filename, binary: false, count: 0 }
+    }
+
+    fn paint<T: Display>(&mut self, color: fn(&Colors) -> &str, text: T) -> io::Result<()> {
+        match self.colors {
+            Some(ref colors) => write!(self.out, "\x1b[{}m{}\x1b[0m", color(colors), text),
+            None => write!(self.out, "{}", text),
+        }
+    }
+
+    /// Write a prefix field such as the path or line number, then `sep`.
+    fn field<T: Display>(&mut self, color: fn(&Colors) -> &str, text: T, sep: char) -> io::Result<()> {
+        self.paint(color, text)?;
+        self.paint(|c| &c.separator, sep)
+    }
+
+    /// Write the line of `m` with every match highlighted, and replaced if
+    /// `--replace` was given.
+    fn highlighted(&mut self, m: &Match) -> io::Result<()> {
+        let line = m.line;
+        let spans = match self.config.replace {
+            Some(ref replacement) => self.query.replacements(line, replacement, self.config.regex),
+            None => matches(self.query, m).into_iter()
+                .map(|(start, end)| (start, end, line[start..end].to_string()))
+                .collect(),
+        };
+        let mut at = 0;
+
+        for (start, end, text) in spans {
+            if start == end && text.is_empty() {
+                continue;
+            }
+            write!(self.out, "{}", &line[at..start])?;
+            self.paint(|c| &c.matched, text)?;
+            at = end;
+        }
+
+        writeln!(self.out, "{}", &line[at..])
+    }
+}
+
+impl<'a, W: Write> Printer for Standard<'a, W> {
+    fn begin(&mut self, _path: &Path, binary: bool) -> io::Result<()> {
+        self.binary = binary;
+        self.count = 0;
+        Ok(())
+    }
+
+    fn event(&mut self, path: &Path, event: &Event) -> io::Result<bool> {
+        let (line_number, byte_offset, column, line, sep) = match *event {
+            Event::Match(ref m) => {
+                self.count += 1;
+                (m.line_number, m.byte_offset, m.column, m.line, ':')
+            },
+            Event::Context(ref c) => (c.line_number, c.byte_offset, 0, c.line, '-'),
+            Event::Break => {
+                self.paint(|c| &c.separator, "--")?;
+                writeln!(self.out)?;
+                return Ok(true);
+            },
+        };
+
+        if self.config.files_with_matches || self.config.files_without_match {
+            return Ok(false);
+        }
+        if self.config.count {
+            return Ok(true);
+        }
+
+        // Binary lines would garble the terminal; say that there was a match
+        // and move on.
+        if self.binary {
+            if sep == ':' {
+                writeln!(self.out, "Binary file {} matches", path.display())?;
+                return Ok(false);
+            }
+            return Ok(true);
+        }
+
+        if self.with_filename {
+            self.field(|c| &c.path, path.display(), sep)?;
+        }
+        if self.config.line_number {
+            self.field(|c| &c.line_number, line_number, sep)?;
+        }
+        if self.config.column && column > 0 {
+            self.field(|c| &c.line_number, column, sep)?;
+        }
+        if self.config.byte_offset {
+            self.field(|c| &c.line_number, byte_offset, sep)?;
+        }
+
+        let marked = self.colors.is_some() || self.config.replace.is_some();
+        match *event {
+            Event::Match(ref m) if marked && !self.config.invert_match => self.highlighted(m)?,
+            _ => writeln!(self.out, "{}", line)?,
+        }
+
+        Ok(true)
+    }
+
+    fn end(&mut self, path: &Path) -> io::Result<Stats> {
+        if self.config.files_with_matches || self.config.files_without_match {
+            if (self.count > 0) == self.config.files_with_matches {
+                self.paint(|c| &c.path, path.display())?;
+                writeln!(self.out)?;
+            }
+        } else if self.config.count {
+            if self.with_filename {
+                self.field(|c| &c.path, path.display(), ':')?;
+            }
+            writeln!(self.out, "{}", self.count)?;
+        }
+
+        Ok(Stats::file(self.count, 0))
+    }
+}
+
+/// JSON Lines output: a `begin` record, one `match` or `context` record per
+/// line and an `end` record for every file. The closing `summary` record is
+/// written by `json_summary`.
+pub struct Json<'a, W> {
+    out: W,
+    query: &'a Query,
+    matched_lines: usize,
+    matches: usize,
+}
+
+impl<'a, W: Write> Json<'a, W> {
+    pub fn new(out: W, query: &'a Query) -> Json<'a, W> {
+        Json { out, query, matched_lines: 0, matches: 0 }
+    }
+}
+
+fn write_json<W: Write>(mut out: W, kind: &str, data: Value) -> io::Result<()> {
+    writeln!(out, "{}", json!({ "type": kind, "data": data }))
+}
+
+/// The `summary` record ending JSON output, with the stats of every file.
+pub fn json_summary<W: Write>(out: W, total: &Stats) -> io::Result<()> {
+    write_json(out, "summary", json!({
+        "stats": {
+            "files_searched": total.files_searched,
+            "files_with_matches": total.files_with_matches,
+            "matched_lines": total.matched_lines,
+            "matches": total.matches,
+        },
+    }))
+}
+
+impl<'a, W: Write> Printer for Json<'a, W> {
+    fn begin(&mut self, path: &Path, _binary: bool) -> io::Result<()> {
+        self.matched_lines = 0;
+        self.matches = 0;
+        write_json(&mut self.out, "begin", json!({ "path": path.to_string_lossy() }))
+    }
+
+    fn event(&mut self, path: &Path, event: &Event) -> io::Result<bool> {
+        match *event {
+            Event::Match(ref m) => {
+                let submatches: Vec<Value> = matches(self.query, m).into_iter()
+                    .map(|(start, end)| json!({
+                        "match": &m.line[start..end],
+                        "start": start,
+                        "end": end,
+                    }))
+                    .collect();
+
+                self.matched_lines += 1;
+                self.matches += submatches.len();
+
+                write_json(&mut self.out, "match", json!({
+                    "path": path.to_string_lossy(),
+                    "line_number": m.line_number,
+                    "absolute_offset": m.byte_offset,
+                    "line": m.line,
+                    "submatches": submatches,
+                }))?;
+            },
+            Event::Context(ref c) => {
+                write_json(&mut self.out, "context", json!({
+                    "path": path.to_string_lossy(),
+                    "line_number": c.line_number,
+                    "absolute_offset": c.byte_offset,
+                    "line": c.line,
+                }))?;
+            },
+            Event::Break => {},
+        }
+
+        Ok(true)
+    }
+
+    fn end(&mut self, path: &Path) -> io::Result<Stats> {
+        write_json(&mut self.out, "end", json!({
+            "path": path.to_string_lossy(),
+            "stats": { "matched_lines": self.matched_lines, "matches": self.matches },
+        }))?;
+
+        Ok(Stats::file(self.matched_lines, self.matches))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use searcher::Searcher;
+
+    #[test]
+    fn highlights_matches() {
+        let config = Config {
+            patterns: vec!["o".to_string()],
+            color: ColorChoice::Always,
+            line_number: true,
+            ..Default::default()
+        };
+        let query = Query::new(&config).unwrap();
+        let path = Path::new("poem.txt");
+        let mut out = Vec::new();
+
+        {
+            let mut printer = Standard::new(&mut out, &config, &query, true);
+            let m = Match { line_number: 2, byte_offset: 25, column: 7, line: "you, too", spans: None };
+
+            printer.event(path, &Event::Match(m)).unwrap();
+        }
+
+        assert_eq!(
+            "\x1b[35mpoem.txt\x1b[0m\x1b[36m:\x1b[0m\x1b[32m2\x1b[0m\x1b[36m:\x1b[0m\
+             y\x1b[1;31mo\x1b[0mu, t\x1b[1;31mo\x1b[0m\x1b[1;31mo\x1b[0m\n",
+            String::from_utf8(out).unwrap(),
+        );
+    }
+
+    #[test]
+    fn json_records() {
+        let query = Query::Literal("o".to_string());
+        let path = Path::new("poem.txt");
+        let mut out = Vec::new();
+
+        let stats = {
+            let mut printer = Json::new(&mut out, &query);
+            let m = Match { line_number: 2, byte_offset: 25, column: 7, line: "Are you nobody", spans: None };
+
+            printer.begin(path, false).unwrap();
+            printer.event(path, &Event::Match(m)).unwrap();
+            printer.end(path).unwrap()
+        };
+        json_summary(&mut out, &stats).unwrap();
+
+        let records: Vec<Value> = String::from_utf8(out).unwrap().lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(4, records.len());
+        assert_eq!(json!({ "type": "begin", "data": { "path": "poem.txt" } }), records[0]);
+        assert_eq!(json!([
+            { "match": "o", "start": 5, "end": 6 },
+            { "match": "o", "start": 9, "end": 10 },
+            { "match": "o", "start": 11, "end": 12 },
+        ]), records[1]["data"]["submatches"]);
+        assert_eq!(json!({ "matched_lines": 1, "matches": 3 }), records[2]["data"]["stats"]);
+        assert_eq!(json!(1), records[3]["data"]["stats"]["files_with_matches"]);
+    }
+
+    #[test]
+    fn multiline_matches() {
+        let config = Config {
+            patterns: vec![r"r\r?\nb".to_string()],
+            regex: true,
+            multiline: true,
+            color: ColorChoice::Always,
+            ..Default::default()
+        };
+        let query = Query::new(&config).unwrap();
+        let path = Path::new("bar.txt");
+        let print = |printer: &mut dyn Printer| {
+            printer.begin(path, false).unwrap();
+            Searcher::new().multiline(true)
+                .search_slice(&query, b"for\r\nbar\n", &mut |event: &Event| printer.event(path, event))
+                .unwrap();
+            printer.end(path).unwrap()
+        };
+
+        let mut out = Vec::new();
+        print(&mut Standard::new(&mut out, &config, &query, false));
+        assert_eq!("fo\x1b[1;31mr\x1b[0m\n\x1b[1;31mb\x1b[0mar\n", String::from_utf8(out).unwrap());
+
+        let mut out = Vec::new();
+        let stats = print(&mut Json::new(&mut out, &query));
+        let records: Vec<Value> = String::from_utf8(out).unwrap().lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(json!([{ "match": "r", "start": 2, "end": 3 }]), records[1]["data"]["submatches"]);
+        assert_eq!(json!([{ "match": "b", "start": 0, "end": 1 }]), records[2]["data"]["submatches"]);
+        assert_eq!((2, 2), (stats.matched_lines, stats.matches));
+    }
+}
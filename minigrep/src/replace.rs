@@ -0,0 +1,131 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::process;
+
+use config::Config;
+use error::Error;
+use input;
+use printer::Stats;
+use search::{trim_terminator, Query};
+
+/// Replace the matches in the file at `path` with `config.replace`, for
+/// `--in-place`. Files without matches, and binary files unless `--text`
+/// was given, are left alone.
+///
+/// The new contents are written next to the file and then renamed over it,
+/// so it is never seen half written. With `config.backup` the original is
+/// first copied to `path` followed by that suffix.
+pub fn rewrite(query: &Query, config: &Config, path: &Path) -> Result<Stats, Error> {
+    let replacement = config.replace.as_ref().expect("--in-place needs --replace");
+    let contents = fs::read_to_string(path).map_err(|e| Error::reading(path, e))?;
+
+    if !config.text && input::is_binary(contents.as_bytes()) {
+        return Ok(Stats::default());
+    }
+
+    let max_count = config.max_count.unwrap_or(usize::MAX);
+    let mut rewritten = String::with_capacity(contents.len());
+    let (mut matched_lines, mut matches) = (0, 0);
+
+    for raw in contents.split_inclusive('\n') {
+        let line = trim_terminator(raw);
+        let spans = if matched_lines < max_count {
+            query.replacements(line, replacement, config.regex)
+        } else {
+            Vec::new()
+        };
+
+        if spans.is_empty() {
+            rewritten.push_str(raw);
+            continue;
+        }
+        matched_lines += 1;
+        matches += spans.len();
+
+        let mut at = 0;
+        for (start, end, text) in spans {
+            rewritten.push_str(&line[at..start]);
+            rewritten.push_str(&text);
+            at = end;
+        }
+        rewritten.push_str(&raw[at..]);
+    }
+
+    if matched_lines > 0 {
+        replace_file(path, rewritten.as_bytes(), config.backup.as_deref())
+            .map_err(|source| Error::Io { path: Some(path.into()), source })?;
+    }
+
+    Ok(Stats::file(matched_lines, matches))
+}
+
+fn replace_file(path: &Path, contents: &[u8], backup: Option<&str>) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = dir.join(format!(".{}.minigrep-{}", name, process::id()));
+
+    let result = (|| {
+        let mut file = File::create(&temp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::set_permissions(&temp, fs::metadata(path)?.permissions())?;
+
+        if let Some(suffix) = backup {
+            let mut backup = path.as_os_str().to_owned();
+            backup.push(suffix);
+            fs::copy(path, backup)?;
+        }
+
+        fs::rename(&temp, path)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn rewrites_matches_and_keeps_backup() {
+        let dir = env::temp_dir().join(format!("minigrep-replace-{}", process::id()));
+        let path = dir.join("poem.txt");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&path, "I'm nobody! Who are you?\r\nThen there's a pair of us\n").unwrap();
+
+        let config = Config {
+            patterns: vec![r"(\w+) are (\w+)".to_string()],
+            regex: true,
+            replace: Some("$2 are $1".to_string()),
+            in_place: true,
+            backup: Some(".bak".to_string()),
+            ..Default::default()
+        };
+        let query = Query::new(&config).unwrap();
+
+        let stats = rewrite(&query, &config, &path).unwrap();
+        assert_eq!((1, 1), (stats.matched_lines, stats.matches));
+        assert_eq!(
+            "I'm nobody! you are Who?\r\nThen there's a pair of us\n",
+            fs::read_to_string(&path).unwrap(),
+        );
+        assert_eq!(
+            "I'm nobody! Who are you?\r\nThen there's a pair of us\n",
+            fs::read_to_string(dir.join("poem.txt.bak")).unwrap(),
+        );
+
+        let stats = rewrite(&Query::Literal("somebody".to_string()), &config, &path).unwrap();
+        assert_eq!(0, stats.matched_lines);
+        assert_eq!(2, fs::read_dir(&dir).unwrap().count());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
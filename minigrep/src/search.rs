@@ -0,0 +1,695 @@
+use std::collections::VecDeque;
+use std::io::{self, prelude::*};
+
+use aho_corasick::{AhoCorasick, MatchKind};
+use memchr::{memchr, memchr_iter, memmem, memrchr};
+use regex::{Regex, RegexBuilder};
+
+use config::Config;
+use error::Error;
+use field::Field;
+use fuzzy::Fuzzy;
+use searcher::Matcher;
+
+/// A line selected by a search.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Match<'a> {
+    /// 1-based number of the line.
+    pub line_number: usize,
+    /// Offset of the start of the line from the start of the input, in bytes.
+    pub byte_offset: usize,
+    /// 1-based column, in bytes, where the first match on the line starts.
+    pub column: usize,
+    /// The line itself, without its terminator.
+    pub line: &'a str,
+    /// Byte ranges of the matches on the line, if the line alone can't
+    /// tell: with `--multiline`, the parts of matches that span lines.
+    /// Otherwise `None`, and the matches are found in the line again.
+    pub spans: Option<&'a [(usize, usize)]>,
+}
+
+/// A line printed around a match because context was asked for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Context<'a> {
+    pub line_number: usize,
+    pub byte_offset: usize,
+    pub line: &'a str,
+}
+
+/// Everything `search_reader` reports, in input order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event<'a> {
+    Match(Match<'a>),
+    Context(Context<'a>),
+    /// Separates two groups of lines that aren't adjacent in the input.
+    Break,
+}
+
+impl<'a> Event<'a> {
+    /// The event numbered as if `lines` lines and `bytes` bytes came before
+    /// the input it is from, for input that starts partway into a file.
+    pub fn shifted(&self, lines: usize, bytes: usize) -> Event<'a> {
+        match *self {
+            Event::Match(m) => Event::Match(Match {
+                line_number: m.line_number + lines,
+                byte_offset: m.byte_offset + bytes,
+                ..m
+            }),
+            Event::Context(c) => Event::Context(Context {
+                line_number: c.line_number + lines,
+                byte_offset: c.byte_offset + bytes,
+                ..c
+            }),
+            Event::Break => Event::Break,
+        }
+    }
+}
+
+/// How much input `search_blocks` reads at a time.
+const BLOCK_LEN: usize = 64 * 1024;
+
+/// Find `query` in all of `contents` at once rather than line by line;
+/// line boundaries are only looked for around each match.
+pub fn search<'a>(query: &str, contents: &'a str) -> Vec<Match<'a>> {
+    let finder = memmem::Finder::new(query);
+    let bytes = contents.as_bytes();
+    let mut matches = Vec::new();
+    let mut line_number = 0;
+    let mut at = 0;
+
+    while at < bytes.len() {
+        let start = match finder.find(&bytes[at..]) {
+            Some(i) => at + i,
+            None => break,
+        };
+        let line_start = memrchr(b'\n', &bytes[at..start]).map_or(at, |i| at + i + 1);
+        let line_end = memchr(b'\n', &bytes[start..]).map_or(bytes.len(), |i| start + i + 1);
+
+        line_number += memchr_iter(b'\n', &bytes[at..line_start]).count() + 1;
+        matches.push(Match {
+            line_number,
+            byte_offset: line_start,
+            column: start - line_start + 1,
+            line: trim_terminator(&contents[line_start..line_end]),
+            spans: None,
+        });
+        at = line_end;
+    }
+
+    matches
+}
+
+/// Match `query` only where it is a whole word: not preceded or followed
+/// by a letter, digit or underscore, in any script.
+pub fn search_whole_words<'a>(query: &str, contents: &'a str) -> Vec<Match<'a>> {
+    let pattern = Regex::new(&whole(&regex::escape(query), true, false))
+        .expect("an escaped literal is a valid pattern");
+
+    search_regex(&pattern, contents)
+}
+
+/// Match only lines that are exactly `query`.
+pub fn search_whole_lines<'a>(query: &str, contents: &'a str) -> Vec<Match<'a>> {
+    search_lines(contents, |line| if line == query { Some(0) } else { None })
+}
+
+/// Match `query` under Unicode simple case folding, so `ẞ` finds `ß`, `Σ`
+/// finds `ς` and the Kelvin sign finds `k`. Lines aren't copied or
+/// lowercased, and columns refer to the original text.
+pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<Match<'a>> {
+    let pattern = case_insensitive_literal(query);
+
+    search_regex(&pattern, contents)
+}
+
+pub fn search_regex<'a>(pattern: &Regex, contents: &'a str) -> Vec<Match<'a>> {
+    search_lines(contents, |line| pattern.find(line).map(|m| m.start()))
+}
+
+fn search_lines<'a, F>(contents: &'a str, mut find: F) -> Vec<Match<'a>>
+    where F: FnMut(&str) -> Option<usize>,
+{
+    let mut matches = Vec::new();
+    let mut byte_offset = 0;
+
+    for (i, raw) in contents.split_inclusive('\n').enumerate() {
+        let line = trim_terminator(raw);
+
+        if let Some(start) = find(line) {
+            matches.push(Match { line_number: i + 1, byte_offset, column: start + 1, line, spans: None });
+        }
+        byte_offset += raw.len();
+    }
+
+    matches
+}
+
+/// Stream `reader` line by line and report each line `find` locates a
+/// match in, as soon as it is read. `find` returns the byte index of the
+/// match within the line.
+///
+/// Up to `before` lines preceding and `after` lines following a match are
+/// reported as context, with a `Break` between groups that aren't adjacent.
+/// `found` returns whether the search should keep going.
+///
+/// Only the current line and the `before` context are held in memory, so
+/// this works on inputs far larger than RAM. Bytes that are not valid
+/// UTF-8 are replaced with U+FFFD rather than ending the search.
+pub fn search_reader<R, M, F>(mut reader: R, before: usize, after: usize,
+                              mut find: M, mut found: F) -> io::Result<()>
+    where R: BufRead,
+          M: FnMut(&str) -> Option<usize>,
+          F: FnMut(Event) -> io::Result<bool>,
+{
+    let mut buf = Vec::new();
+    let mut line_number = 0;
+    let mut byte_offset = 0;
+    // Lines seen since the last reported one, kept for `before` context.
+    let mut pending: VecDeque<(usize, usize, String)> = VecDeque::with_capacity(before);
+    let mut after_left = 0;
+    let mut last_reported = None;
+
+    loop {
+        buf.clear();
+        let read = reader.read_until(b'\n', &mut buf)?;
+        if read == 0 {
+            return Ok(());
+        }
+        line_number += 1;
+
+        let line = String::from_utf8_lossy(&buf);
+        let line = trim_terminator(&line);
+
+        if let Some(start) = find(line) {
+            let first = line_number - pending.len();
+            if before + after > 0 && last_reported.is_some_and(|last| first > last + 1)
+                && !found(Event::Break)? {
+                return Ok(());
+            }
+
+            for (line_number, byte_offset, line) in pending.drain(..) {
+                if !found(Event::Context(Context { line_number, byte_offset, line: &line }))? {
+                    return Ok(());
+                }
+            }
+
+            let m = Match { line_number, byte_offset, column: start + 1, line, spans: None };
+            if !found(Event::Match(m))? {
+                return Ok(());
+            }
+
+            after_left = after;
+            last_reported = Some(line_number);
+        } else if after_left > 0 {
+            if !found(Event::Context(Context { line_number, byte_offset, line }))? {
+                return Ok(());
+            }
+
+            after_left -= 1;
+            last_reported = Some(line_number);
+        } else if before > 0 {
+            let mut saved = if pending.len() == before {
+                pending.pop_front().map(|(_, _, line)| line).unwrap_or_default()
+            } else {
+                String::new()
+            };
+            saved.clear();
+            saved.push_str(line);
+            pending.push_back((line_number, byte_offset, saved));
+        }
+
+        byte_offset += read;
+    }
+}
+
+/// Report the lines `find` locates a match in, like `search_reader` with
+/// no context, but read `reader` in large blocks and let `candidate` skip
+/// ahead within each block.
+///
+/// `candidate` is given whole lines and returns the offset of the first
+/// byte that could be part of a match, or `None` if none of them can match.
+/// Only the line holding that byte is decoded and passed to `find`, so with
+/// a fast `candidate` most of the input is never looked at line by line.
+pub fn search_blocks<R, C, M, F>(mut reader: R, mut candidate: C, mut find: M,
+                                 mut found: F) -> io::Result<()>
+    where R: Read,
+          C: FnMut(&[u8]) -> Option<usize>,
+          M: FnMut(&str) -> Option<usize>,
+          F: FnMut(Event) -> io::Result<bool>,
+{
+    let mut buf = Vec::with_capacity(BLOCK_LEN);
+    let mut line_number = 0;
+    // Offset of `buf[0]` in the input.
+    let mut byte_offset = 0;
+
+    loop {
+        let filled = buf.len();
+        buf.resize(filled + BLOCK_LEN, 0);
+        let read = loop {
+            match reader.read(&mut buf[filled..]) {
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                result => break result?,
+            }
+        };
+        buf.truncate(filled + read);
+
+        let eof = read == 0;
+        // Only whole lines are searched; the rest waits for the next read.
+        let end = match memrchr(b'\n', &buf[filled..]) {
+            _ if eof => buf.len(),
+            Some(i) => filled + i + 1,
+            None => continue,
+        };
+        let mut at = 0;
+
+        while at < end {
+            let start = match candidate(&buf[at..end]) {
+                Some(i) => at + i,
+                None => break,
+            };
+            let line_start = memrchr(b'\n', &buf[at..start]).map_or(at, |i| at + i + 1);
+            let line_end = memchr(b'\n', &buf[start..end]).map_or(end, |i| start + i + 1);
+
+            line_number += memchr_iter(b'\n', &buf[at..line_start]).count() + 1;
+            at = line_end;
+
+            let line = String::from_utf8_lossy(&buf[line_start..line_end]);
+            let line = trim_terminator(&line);
+
+            if let Some(start) = find(line) {
+                let m = Match { line_number, byte_offset: byte_offset + line_start, column: start + 1, line, spans: None };
+                if !found(Event::Match(m))? {
+                    return Ok(());
+                }
+            }
+        }
+
+        if eof {
+            return Ok(());
+        }
+        line_number += memchr_iter(b'\n', &buf[at..end]).count();
+        byte_offset += end;
+        buf.drain(..end);
+    }
+}
+
+/// `pattern` restricted to whole words or whole lines, for `-w` and `-x`.
+/// A word is bounded by anything but a word character, so `-w` works for
+/// patterns that start or end with punctuation too.
+fn whole(pattern: &str, word: bool, line: bool) -> String {
+    if line {
+        format!("^(?:{})$", pattern)
+    } else if word {
+        format!(r"\b{{start-half}}(?:{})\b{{end-half}}", pattern)
+    } else {
+        pattern.to_string()
+    }
+}
+
+/// `line` without its `\n` or `\r\n`.
+pub fn trim_terminator(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+/// A regex matching `query` literally, with case folded the way the regex
+/// engine does it: Unicode simple case folding, one char to one char.
+fn case_insensitive_literal(query: &str) -> Regex {
+    RegexBuilder::new(&regex::escape(query))
+        .case_insensitive(true)
+        .build()
+        .expect("an escaped literal is a valid pattern")
+}
+
+/// Whether `pattern` has an uppercase letter, for smart case. In a regex,
+/// escapes such as `\W` or `\S` don't count.
+fn has_uppercase(pattern: &str, regex: bool) -> bool {
+    let mut escaped = false;
+
+    pattern.chars().any(|c| {
+        let upper = c.is_uppercase() && !escaped;
+        escaped = regex && c == '\\' && !escaped;
+        upper
+    })
+}
+
+/// Whether `config` asks for case-sensitive matching, after smart case.
+pub fn case_sensitive(config: &Config) -> bool {
+    if config.smart_case {
+        config.patterns.iter().any(|pattern| has_uppercase(pattern, config.regex))
+    } else {
+        config.case_sensitive
+    }
+}
+
+/// The query from a `Config`, prepared once before searching.
+#[derive(Clone)]
+pub enum Query {
+    Literal(String),
+    /// Several literals, found together with Aho-Corasick.
+    Literals(AhoCorasick),
+    /// Patterns matched approximately, for `--fuzzy`.
+    Fuzzy(Fuzzy),
+    /// Regular expressions, and literals matched case-insensitively.
+    Regex(Regex),
+    /// A query matched against one field of each line, for `--field`.
+    Field(Box<Query>, Field),
+}
+
+impl Query {
+    /// Prepare every pattern in `config` to be matched in a single pass,
+    /// against the field `--field` picks if one is given.
+    pub fn new(config: &Config) -> Result<Query, Error> {
+        let query = Query::patterns(config)?;
+
+        Ok(match Field::new(config) {
+            Some(field) => Query::Field(Box::new(query), field),
+            None => query,
+        })
+    }
+
+    fn patterns(config: &Config) -> Result<Query, Error> {
+        let patterns = &config.patterns;
+        let case_sensitive = case_sensitive(config);
+
+        if let Some(max_distance) = config.fuzzy {
+            return Ok(Query::Fuzzy(Fuzzy::new(patterns, max_distance, case_sensitive)));
+        }
+        // No patterns at all (e.g. `-f /dev/null`) go to Aho-Corasick too,
+        // which then finds nothing, where an empty alternation finds a match
+        // on every line.
+        let literal = patterns.is_empty()
+            || case_sensitive && !config.regex && !config.word_regexp && !config.line_regexp;
+
+        if patterns.len() == 1 && literal {
+            return Ok(Query::Literal(patterns[0].clone()));
+        }
+        if literal {
+            // Leftmost-first, so the earlier pattern wins at a position like
+            // it does in the regex alternation below.
+            let literals = AhoCorasick::builder()
+                .match_kind(MatchKind::LeftmostFirst)
+                .build(patterns);
+
+            if let Ok(literals) = literals {
+                return Ok(Query::Literals(literals));
+            }
+        }
+
+        let alternation = patterns.iter()
+            .map(|pattern| if config.regex {
+                format!("(?:{})", pattern)
+            } else {
+                regex::escape(pattern)
+            })
+            .collect::<Vec<_>>()
+            .join("|");
+        let alternation = whole(&alternation, config.word_regexp, config.line_regexp);
+
+        let build = |pattern: &str| RegexBuilder::new(pattern)
+            .case_insensitive(!case_sensitive)
+            .multi_line(config.multiline)
+            .build();
+
+        match build(&alternation) {
+            Ok(pattern) => Ok(Query::Regex(pattern)),
+            Err(source) => {
+                // Blame the pattern that is broken on its own, if there is one.
+                for pattern in patterns {
+                    if let Err(source) = build(pattern) {
+                        return Err(Error::InvalidPattern { pattern: pattern.clone(), source });
+                    }
+                }
+                Err(Error::InvalidPattern { pattern: alternation, source })
+            },
+        }
+    }
+
+    /// Whether matches are approximate, and can be ranked by `distance`.
+    pub fn is_fuzzy(&self) -> bool {
+        match *self {
+            Query::Fuzzy(_) => true,
+            Query::Field(ref query, _) => query.is_fuzzy(),
+            _ => false,
+        }
+    }
+
+    /// How far the closest match in `line` is from a fuzzy query, in the
+    /// field `--field` picks if there is one.
+    pub fn distance(&self, line: &str) -> Option<usize> {
+        match *self {
+            Query::Fuzzy(ref fuzzy) => fuzzy.best(line).map(|best| best.distance),
+            Query::Field(ref query, ref field) => query.distance(&field.value(line)?),
+            _ => None,
+        }
+    }
+
+    /// Byte range of every match in `line`, with the text `replacement`
+    /// turns into there. With `expand`, `$1` or `${name}` in `replacement`
+    /// stand for a capture group of the regex; otherwise it is used as is.
+    pub fn replacements(&self, line: &str, replacement: &str, expand: bool) -> Vec<(usize, usize, String)> {
+        match *self {
+            Query::Regex(ref pattern) if expand => pattern.captures_iter(line)
+                .map(|captures| {
+                    let whole = captures.get(0).expect("group 0 is the whole match");
+                    let mut text = String::new();
+
+                    captures.expand(replacement, &mut text);
+                    (whole.start(), whole.end(), text)
+                })
+                .collect(),
+            _ => self.find_all(line).into_iter()
+                .map(|(start, end)| (start, end, replacement.to_string()))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lines<'a>(matches: Vec<Match<'a>>) -> Vec<&'a str> {
+        matches.into_iter().map(|m| m.line).collect()
+    }
+
+    #[test]
+    fn case_sensitive() {
+        let query = "duct";
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Duct tape.";
+
+        assert_eq!(
+            vec![Match { line_number: 2, byte_offset: 6, column: 16, line: "safe, fast, productive.", spans: None }],
+            search(query, contents),
+        );
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let query = "rUsT";
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Trust me.";
+
+        assert_eq!(
+            vec!["Rust:", "Trust me."],
+            lines(search_case_insensitive(query, contents)),
+        );
+    }
+
+    #[test]
+    fn case_insensitive_folds_unicode() {
+        let contents = "\
+STRAẞE
+ὈΔΥΣΣΕΎΣ
+200 \u{212A}B";
+
+        assert_eq!(vec!["STRAẞE"], lines(search_case_insensitive("straße", contents)));
+        assert_eq!(vec!["ὈΔΥΣΣΕΎΣ"], lines(search_case_insensitive("ὀδυσσεύς", contents)));
+        assert_eq!(vec!["200 \u{212A}B"], lines(search_case_insensitive("kb", contents)));
+    }
+
+    #[test]
+    fn case_insensitive_column_in_original_text() {
+        // 'İ' lowercases to two chars, which must not shift the column.
+        let matches = search_case_insensitive("rust", "İ RUST");
+
+        assert_eq!(4, matches[0].column);
+
+        let query = Query::Regex(case_insensitive_literal("rust"));
+        assert_eq!(vec![(3, 7), (8, 12)], query.find_all("İ RUST rust"));
+    }
+
+    #[test]
+    fn regex_word_boundary() {
+        let pattern = Regex::new(r"\b(fast|three)\b").unwrap();
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Threefold.";
+
+        assert_eq!(
+            vec!["safe, fast, productive.", "Pick three."],
+            lines(search_regex(&pattern, contents)),
+        );
+    }
+
+    fn query(args: &[&str]) -> Query {
+        let args = args.iter().map(|arg| arg.to_string());
+
+        match ::Command::parse(args).unwrap() {
+            ::Command::Search(config) => Query::new(&config).unwrap(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn smart_case() {
+        assert!(query(&["minigrep", "-S", "rust", "."]).find("Trust me.").is_some());
+        assert!(query(&["minigrep", "-S", "Rust", "."]).find("Trust me.").is_none());
+        assert!(query(&["minigrep", "-SE", r"\Wrust", "."]).find("I Rust").is_some());
+    }
+
+    #[test]
+    fn several_patterns_in_one_pass() {
+        let query = query(&["minigrep", "-e", "fast", "-e", "a.r", "-s", "."]);
+
+        assert_eq!(vec![(6, 10)], query.find_all("safe, fast, productive."));
+        assert_eq!(vec![(5, 8)], query.find_all("Pick a.r"));
+        assert!(query.find("Pick air").is_none());
+        assert!(matches!(query, Query::Literals(_)));
+
+        let err = Query::new(&::Config {
+            patterns: vec!["ok".to_string(), "(".to_string()],
+            regex: true,
+            ..Default::default()
+        });
+        match err {
+            Err(Error::InvalidPattern { pattern, .. }) => assert_eq!("(", pattern),
+            other => panic!("expected an invalid pattern, got {:?}", other.err()),
+        }
+
+        let nothing = Query::new(&::Config { case_sensitive: false, regex: true, ..Default::default() }).unwrap();
+        assert!(nothing.find("anything").is_none());
+    }
+
+    #[test]
+    fn whole_words_and_lines() {
+        let contents = "run();\nruntime\ntrunk\nnaïve run\n";
+        let lines = |matches: Vec<Match>| matches.iter().map(|m| m.line_number).collect::<Vec<_>>();
+
+        assert_eq!(vec![1, 4], lines(search_whole_words("run", contents)));
+        assert!(search_whole_words("na", contents).is_empty());
+        assert_eq!(vec![2], lines(search_whole_lines("runtime", contents)));
+
+        let words = query(&["minigrep", "-wi", "-e", "RUN", "-e", "--fix", "."]);
+        assert_eq!(vec![(0, 3), (8, 13)], words.find_all("Run it, --fix it, --fixup"));
+
+        let line = query(&["minigrep", "-x", "trunk", "."]);
+        assert_eq!(Some((0, 5)), line.find("trunk"));
+        assert!(line.find("trunk road").is_none());
+    }
+
+    #[test]
+    fn replacements_expand_captures_in_regex_mode() {
+        let regex = Query::new(&::Config {
+            patterns: vec![r"(\w+)@(\w+)".to_string()],
+            regex: true,
+            ..Default::default()
+        }).unwrap();
+        let literal = Query::Literal("$1".to_string());
+
+        assert_eq!(
+            vec![(3, 9, "bob at me".to_string())],
+            regex.replacements("to bob@me", "$1 at $2", true),
+        );
+        assert_eq!(vec![(4, 6, "${x}".to_string())], literal.replacements("pay $1", "${x}", false));
+    }
+
+    fn events(contents: &str, query: &str, before: usize, after: usize) -> Vec<String> {
+        let mut events = Vec::new();
+
+        search_reader(contents.as_bytes(), before, after, |line| line.find(query), |event| {
+            events.push(match event {
+                Event::Match(m) => format!("{}:{}:{}", m.line_number, m.byte_offset, m.line),
+                Event::Context(c) => format!("{}-{}-{}", c.line_number, c.byte_offset, c.line),
+                Event::Break => "--".to_string(),
+            });
+            Ok(true)
+        }).unwrap();
+
+        events
+    }
+
+    #[test]
+    fn reader_streams_matches() {
+        let contents = "Rust:\r\nsafe, fast, productive.\nPick three.\nTrust me.";
+
+        assert_eq!(vec!["1:0:Rust:", "4:43:Trust me."], events(contents, "ust", 0, 0));
+    }
+
+    #[test]
+    fn blocks_agree_with_lines() {
+        // Several blocks' worth, so lines straddle block boundaries.
+        let contents: String = (0..20_000)
+            .map(|i| if i % 997 == 0 { format!("line {} has a frog\n", i) } else { format!("line {}\n", i) })
+            .collect();
+        let found = |blocks: bool| {
+            let mut lines = Vec::new();
+            let record = |event: Event| {
+                if let Event::Match(m) = event {
+                    lines.push(format!("{}:{}:{}:{}", m.line_number, m.byte_offset, m.column, m.line));
+                }
+                Ok(true)
+            };
+            let find = |line: &str| line.find("frog");
+
+            if blocks {
+                search_blocks(contents.as_bytes(), |block| memmem::find(block, b"frog"), find, record).unwrap();
+            } else {
+                search_reader(contents.as_bytes(), 0, 0, find, record).unwrap();
+            }
+            lines
+        };
+
+        assert_eq!(21, found(true).len());
+        assert_eq!(found(false), found(true));
+        assert_eq!(search("frog", &contents), search_regex(&Regex::new("frog").unwrap(), &contents));
+    }
+
+    #[test]
+    fn reader_survives_invalid_utf8() {
+        let contents = b"Caf\xe9 Rust\nTrust me.";
+        let mut lines = Vec::new();
+
+        search_reader(&contents[..], 0, 0, |line| line.find("ust"), |event| {
+            if let Event::Match(m) = event {
+                lines.push(m.line.to_string());
+            }
+            Ok(true)
+        }).unwrap();
+
+        assert_eq!(vec!["Caf\u{fffd} Rust", "Trust me."], lines);
+    }
+
+    #[test]
+    fn reader_context_groups() {
+        let contents = "a\nb\nmatch\nc\nd\ne\nf\nmatch\ng\nmatch\nh";
+
+        assert_eq!(
+            vec![
+                "2-2-b", "3:4:match", "4-10-c",
+                "--",
+                "7-16-f", "8:18:match", "9-24-g", "10:26:match", "11-32-h",
+            ],
+            events(contents, "match", 1, 1),
+        );
+    }
+}
@@ -0,0 +1,403 @@
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::io::{self, prelude::*};
+
+use aho_corasick::AhoCorasick;
+use memchr::{memchr, memchr_iter, memmem, memrchr};
+use regex::Regex;
+
+use search::{search_blocks, search_reader, Event, Match, Query};
+
+/// Finds matches within a single line.
+pub trait Matcher {
+    /// Byte range of the first match in `line`, if any.
+    fn find(&self, line: &str) -> Option<(usize, usize)>;
+
+    /// Byte ranges of every non-overlapping match in `line`. The default
+    /// calls `find` on what is left of the line after each match.
+    fn find_all(&self, line: &str) -> Vec<(usize, usize)> {
+        let mut matches = Vec::new();
+        let mut at = 0;
+
+        while let Some((start, end)) = self.find(&line[at..]) {
+            matches.push((at + start, at + end));
+            at += end.max(start + 1);
+            while at < line.len() && !line.is_char_boundary(at) {
+                at += 1;
+            }
+            if at > line.len() {
+                break;
+            }
+        }
+
+        matches
+    }
+
+    /// Offset in `haystack`, a block of whole lines, of the first byte that
+    /// could be part of a match, or `None` if no line in it can match. Lines
+    /// before that byte are skipped without calling `find`. By default any
+    /// line could match.
+    fn candidate(&self, _haystack: &[u8]) -> Option<usize> {
+        Some(0)
+    }
+}
+
+impl Matcher for Query {
+    fn find(&self, line: &str) -> Option<(usize, usize)> {
+        match *self {
+            Query::Literal(ref query) => Matcher::find(query.as_str(), line),
+            Query::Literals(ref literals) => Matcher::find(literals, line),
+            Query::Fuzzy(ref fuzzy) => fuzzy.find(line),
+            Query::Regex(ref pattern) => Matcher::find(pattern, line),
+            Query::Field(ref query, ref field) => field.find(&**query, line),
+        }
+    }
+
+    fn find_all(&self, line: &str) -> Vec<(usize, usize)> {
+        match *self {
+            Query::Literal(ref query) => query.as_str().find_all(line),
+            Query::Literals(ref literals) => literals.find_all(line),
+            Query::Fuzzy(ref fuzzy) => fuzzy.find_all(line),
+            Query::Regex(ref pattern) => pattern.find_all(line),
+            Query::Field(ref query, ref field) => field.find_all(&**query, line),
+        }
+    }
+
+    fn candidate(&self, haystack: &[u8]) -> Option<usize> {
+        match *self {
+            Query::Literal(ref query) => query.as_str().candidate(haystack),
+            Query::Literals(ref literals) => literals.candidate(haystack),
+            Query::Fuzzy(_) | Query::Regex(_) => Some(0),
+            // Unquoting can change a field's text, so any line may match.
+            Query::Field(..) => Some(0),
+        }
+    }
+}
+
+impl Matcher for AhoCorasick {
+    fn find(&self, line: &str) -> Option<(usize, usize)> {
+        AhoCorasick::find(self, line).map(|m| (m.start(), m.end()))
+    }
+
+    fn find_all(&self, line: &str) -> Vec<(usize, usize)> {
+        self.find_iter(line).map(|m| (m.start(), m.end())).collect()
+    }
+
+    fn candidate(&self, haystack: &[u8]) -> Option<usize> {
+        AhoCorasick::find(self, haystack).map(|m| m.start())
+    }
+}
+
+impl Matcher for Regex {
+    fn find(&self, line: &str) -> Option<(usize, usize)> {
+        Regex::find(self, line).map(|m| (m.start(), m.end()))
+    }
+
+    fn find_all(&self, line: &str) -> Vec<(usize, usize)> {
+        self.find_iter(line).map(|m| (m.start(), m.end())).collect()
+    }
+}
+
+/// A literal, case-sensitive string.
+impl Matcher for str {
+    fn find(&self, line: &str) -> Option<(usize, usize)> {
+        line.find(self).map(|i| (i, i + self.len()))
+    }
+
+    fn candidate(&self, haystack: &[u8]) -> Option<usize> {
+        // Undecodable bytes only become U+FFFD once a line is decoded.
+        if self.contains(char::REPLACEMENT_CHARACTER) {
+            return Some(0);
+        }
+        memmem::find(haystack, self.as_bytes())
+    }
+}
+
+/// Receives what a `Searcher` finds, in input order.
+pub trait Sink {
+    /// Called for every event. Returns whether the search should keep going.
+    fn event(&mut self, event: &Event) -> io::Result<bool>;
+}
+
+impl<F> Sink for F where F: FnMut(&Event) -> io::Result<bool> {
+    fn event(&mut self, event: &Event) -> io::Result<bool> {
+        self(event)
+    }
+}
+
+/// Searches line-oriented input with a `Matcher`, handing lines to a `Sink`
+/// rather than printing them.
+///
+/// ```
+/// use minigrep::{Event, Searcher};
+///
+/// let mut lines = Vec::new();
+/// Searcher::new()
+///     .after_context(1)
+///     .search_slice("body", b"I'm nobody!\nWho are you?\nAre you nobody, too?\n", &mut |event: &Event| {
+///         if let Event::Match(ref m) = *event {
+///             lines.push(m.line_number);
+///         }
+///         Ok(true)
+///     })
+///     .unwrap();
+///
+/// assert_eq!(vec![1, 3], lines);
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Searcher {
+    before_context: usize,
+    after_context: usize,
+    invert_match: bool,
+    max_count: Option<usize>,
+    multiline: bool,
+}
+
+impl Searcher {
+    /// A searcher reporting only matching lines, all of them.
+    pub fn new() -> Searcher {
+        Searcher::default()
+    }
+
+    /// Report up to `lines` lines before each match as context.
+    pub fn before_context(mut self, lines: usize) -> Searcher {
+        self.before_context = lines;
+        self
+    }
+
+    /// Report up to `lines` lines after each match as context.
+    pub fn after_context(mut self, lines: usize) -> Searcher {
+        self.after_context = lines;
+        self
+    }
+
+    /// Select the lines that don't match instead.
+    pub fn invert_match(mut self, yes: bool) -> Searcher {
+        self.invert_match = yes;
+        self
+    }
+
+    /// Stop after this many selected lines, once their trailing context
+    /// has been reported.
+    pub fn max_count(mut self, max: Option<usize>) -> Searcher {
+        self.max_count = max;
+        self
+    }
+
+    /// Let matches span several lines, in which case every line a match
+    /// touches is selected. The whole input is read into memory first.
+    pub fn multiline(mut self, yes: bool) -> Searcher {
+        self.multiline = yes;
+        self
+    }
+
+    /// Search `reader` as it is read; see `search_reader` for how lines
+    /// are decoded and grouped.
+    pub fn search_reader<M, R, S>(&self, matcher: &M, mut reader: R, sink: &mut S) -> io::Result<()>
+        where M: Matcher + ?Sized,
+              R: BufRead,
+              S: Sink + ?Sized,
+    {
+        if self.multiline {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes)?;
+
+            let text = String::from_utf8_lossy(&bytes);
+            let spanned = spanned_lines(matcher, &text);
+            let mut line_index = 0;
+
+            // Lines come by in order, one call each.
+            return self.search_lines(&bytes[..], |_| {
+                line_index += 1;
+                spanned.get(&(line_index - 1)).map(|spans| spans[0].0)
+            }, &mut |event: &Event| match *event {
+                // The printer can't find these in the line by itself.
+                Event::Match(m) => {
+                    let spans = spanned.get(&(m.line_number - 1)).map(Vec::as_slice);
+                    sink.event(&Event::Match(Match { spans, ..m }))
+                },
+                _ => sink.event(event),
+            });
+        }
+
+        // Without context or inversion, lines that can't match needn't be
+        // looked at one by one.
+        if self.before_context == 0 && self.after_context == 0 && !self.invert_match {
+            let max_count = self.max_count.unwrap_or(usize::MAX);
+            let mut selected = 0;
+
+            if max_count == 0 {
+                return Ok(());
+            }
+            return search_blocks(
+                reader,
+                |block| matcher.candidate(block),
+                |line| matcher.find(line).map(|(start, _)| start),
+                |event| {
+                    let keep_going = sink.event(&event)?;
+                    selected += 1;
+                    Ok(keep_going && selected < max_count)
+                },
+            );
+        }
+
+        self.search_lines(reader, |line| matcher.find(line).map(|(start, _)| start), sink)
+    }
+
+    /// Search line by line, selecting the lines `find` returns the start
+    /// of a match for.
+    fn search_lines<R, F, S>(&self, reader: R, mut find: F, sink: &mut S) -> io::Result<()>
+        where R: BufRead,
+              F: FnMut(&str) -> Option<usize>,
+              S: Sink + ?Sized,
+    {
+        let max_count = self.max_count.unwrap_or(usize::MAX);
+        let selected = Cell::new(0);
+        let mut trailing = 0;
+
+        search_reader(
+            reader,
+            self.before_context,
+            self.after_context,
+            |line| match find(line) {
+                _ if selected.get() >= max_count => None,
+                Some(_) if self.invert_match => None,
+                None if self.invert_match => Some(0),
+                found => found,
+            },
+            |event| {
+                let keep_going = sink.event(&event)?;
+
+                match event {
+                    Event::Match(_) => {
+                        selected.set(selected.get() + 1);
+                        trailing = 0;
+                    },
+                    Event::Context(_) => trailing += 1,
+                    Event::Break => {},
+                }
+
+                // After the last allowed match only its trailing context is left.
+                Ok(keep_going && !(selected.get() >= max_count && trailing >= self.after_context))
+            },
+        )
+    }
+
+    /// Search input that is already in memory.
+    pub fn search_slice<M, S>(&self, matcher: &M, bytes: &[u8], sink: &mut S) -> io::Result<()>
+        where M: Matcher + ?Sized,
+              S: Sink + ?Sized,
+    {
+        self.search_reader(matcher, bytes, sink)
+    }
+}
+
+/// The 0-based index of every line touched by a match in `text`, with the
+/// byte ranges in that line of the matches, or of the parts of them on it.
+fn spanned_lines<M: Matcher + ?Sized>(matcher: &M, text: &str) -> BTreeMap<usize, Vec<(usize, usize)>> {
+    let bytes = text.as_bytes();
+    let mut lines: BTreeMap<usize, Vec<(usize, usize)>> = BTreeMap::new();
+    // Line index of `bytes[at]`.
+    let (mut line, mut at) = (0, 0);
+
+    for (start, end) in matcher.find_all(text) {
+        line += memchr_iter(b'\n', &bytes[at..start]).count();
+        let mut line_start = memrchr(b'\n', &bytes[..start]).map_or(0, |i| i + 1);
+
+        loop {
+            // The line without its terminator, which isn't highlighted.
+            let terminator = memchr(b'\n', &bytes[line_start..]).map(|i| line_start + i);
+            let mut line_end = terminator.unwrap_or(bytes.len());
+            if line_end > line_start && bytes[line_end - 1] == b'\r' {
+                line_end -= 1;
+            }
+
+            let span = (start.max(line_start) - line_start, end.min(line_end).max(line_start) - line_start);
+            lines.entry(line).or_default().push(span);
+
+            // A match ending in a line terminator doesn't touch the next line.
+            match terminator {
+                Some(terminator) if terminator + 1 < end => {
+                    line += 1;
+                    line_start = terminator + 1;
+                },
+                _ => break,
+            }
+        }
+        at = line_start;
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Matches lines longer than its length.
+    struct LongerThan(usize);
+
+    impl Matcher for LongerThan {
+        fn find(&self, line: &str) -> Option<(usize, usize)> {
+            if line.len() > self.0 { Some((0, line.len())) } else { None }
+        }
+    }
+
+    fn lines<M: Matcher + ?Sized>(searcher: Searcher, matcher: &M, contents: &str) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        searcher.search_slice(matcher, contents.as_bytes(), &mut |event: &Event| {
+            lines.push(match *event {
+                Event::Match(m) => format!("{}:{}", m.line_number, m.line),
+                Event::Context(c) => format!("{}-{}", c.line_number, c.line),
+                Event::Break => "--".to_string(),
+            });
+            Ok(true)
+        }).unwrap();
+
+        lines
+    }
+
+    #[test]
+    fn custom_matcher() {
+        let contents = "short\na longer line\ntiny\n";
+
+        assert_eq!(vec!["2:a longer line"], lines(Searcher::new(), &LongerThan(5), contents));
+        assert_eq!(
+            vec!["1:short", "3:tiny"],
+            lines(Searcher::new().invert_match(true), &LongerThan(5), contents),
+        );
+        assert_eq!(vec![(1, 2), (6, 7), (7, 8)], "o".find_all("you, too"));
+    }
+
+    #[test]
+    fn multiline_matches_select_every_line_they_touch() {
+        let contents = "fn run(\n    config: Config,\n) -> Result {\n}\nfn main() {}\n";
+        let signature = Regex::new(r"(?m)fn \w+\([^)]*\)").unwrap();
+
+        assert_eq!(
+            vec!["1:fn run(", "2:    config: Config,", "3:) -> Result {", "4-}", "5:fn main() {}"],
+            lines(Searcher::new().multiline(true).after_context(1), &signature, contents),
+        );
+        assert_eq!(
+            vec!["4:}"],
+            lines(Searcher::new().multiline(true).invert_match(true), &signature, contents),
+        );
+        assert_eq!(vec!["1:fn run("], lines(Searcher::new().multiline(true), "run(\n", contents));
+    }
+
+    #[test]
+    fn builder_options() {
+        let contents = "a1\nb\na2\nc\na3\n";
+        let pattern = Regex::new("a").unwrap();
+
+        assert_eq!(
+            vec!["1:a1", "2-b", "3:a2", "4-c"],
+            lines(Searcher::new().after_context(1).max_count(Some(2)), "a", contents),
+        );
+        assert_eq!(
+            vec!["1-a1", "2:b", "3-a2", "4:c"],
+            lines(Searcher::new().before_context(1).invert_match(true), &pattern, contents),
+        );
+    }
+}
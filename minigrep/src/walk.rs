@@ -0,0 +1,156 @@
+use std::path::{Path, PathBuf};
+
+use ignore::WalkBuilder;
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::types::{Types, TypesBuilder};
+
+use error::Error;
+
+#[derive(Debug, Default, Clone)]
+pub struct WalkOptions {
+    pub hidden: bool,
+    pub no_ignore: bool,
+    /// Globs files must match, or with a leading `!` must not.
+    pub globs: Vec<String>,
+    /// File types to search; empty means all of them.
+    pub types: Vec<String>,
+    pub types_not: Vec<String>,
+    /// Extra type definitions such as `web:*.{html,css}`.
+    pub type_defs: Vec<String>,
+}
+
+impl WalkOptions {
+    /// The built-in file types, plus the ones `type_defs` adds, with
+    /// `types` and `types_not` applied.
+    pub fn file_types(&self) -> Result<Types, Error> {
+        let mut builder = TypesBuilder::new();
+        builder.add_defaults();
+
+        for def in &self.type_defs {
+            builder.add_def(def)
+                .map_err(|e| Error::InvalidArgument(format!("invalid file type '{}': {}", def, e)))?;
+        }
+        for name in &self.types {
+            builder.select(name);
+        }
+        for name in &self.types_not {
+            builder.negate(name);
+        }
+
+        builder.build().map_err(|e| Error::InvalidArgument(e.to_string()))
+    }
+
+    /// `globs`, relative to `root`.
+    fn overrides<P: AsRef<Path>>(&self, root: P) -> Result<Override, Error> {
+        let mut builder = OverrideBuilder::new(root);
+
+        for glob in &self.globs {
+            builder.add(glob)
+                .map_err(|e| Error::InvalidArgument(format!("invalid glob '{}': {}", glob, e)))?;
+        }
+
+        builder.build().map_err(|e| Error::InvalidArgument(e.to_string()))
+    }
+
+    /// Make sure every glob and file type is usable before searching.
+    pub fn check(&self) -> Result<(), Error> {
+        self.file_types()?;
+        self.overrides(".")?;
+        Ok(())
+    }
+}
+
+/// Collect every file below `root`, sorted by path.
+///
+/// `.gitignore` and `.ignore` rules are honored and hidden entries are
+/// skipped unless `options` says otherwise, and only files passing its
+/// globs and file types are kept. Entries that cannot be read are reported
+/// on stderr and left out.
+pub fn files<P: AsRef<Path>>(root: P, options: &WalkOptions) -> Result<Vec<PathBuf>, Error> {
+    let walker = WalkBuilder::new(&root)
+        .hidden(!options.hidden)
+        .ignore(!options.no_ignore)
+        .git_ignore(!options.no_ignore)
+        .git_global(!options.no_ignore)
+        .git_exclude(!options.no_ignore)
+        .require_git(false)
+        .overrides(options.overrides(&root)?)
+        .types(options.file_types()?)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .build();
+
+    let mut files = Vec::new();
+
+    for entry in walker {
+        match entry {
+            Ok(entry) => {
+                if entry.file_type().is_some_and(|t| t.is_file()) {
+                    files.push(entry.into_path());
+                }
+            },
+            Err(e) => eprintln!("minigrep: {}", e),
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{env, fs, process};
+
+    #[test]
+    fn honors_ignore_rules_and_hidden_files() {
+        let root = env::temp_dir().join(format!("minigrep-walk-{}", process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        fs::write(root.join(".hidden"), "secret").unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(root.join("target/out.rs"), "fn main() {}").unwrap();
+
+        let found = files(&root, &WalkOptions::default()).unwrap();
+        let all = files(&root, &WalkOptions { hidden: true, no_ignore: true, ..Default::default() }).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(vec![root.join("src/main.rs")], found);
+        assert_eq!(4, all.len());
+    }
+
+    #[test]
+    fn filters_by_glob_and_type() {
+        let root = env::temp_dir().join(format!("minigrep-filter-{}", process::id()));
+        fs::create_dir_all(root.join("src/gen")).unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(root.join("src/gen/table.rs"), "const T: u8 = 0;").unwrap();
+        fs::write(root.join("src/app.js"), "main();").unwrap();
+        fs::write(root.join("README.md"), "# main").unwrap();
+
+        let search = |options: WalkOptions| -> Vec<PathBuf> {
+            files(&root, &options).unwrap().into_iter()
+                .map(|path| path.strip_prefix(&root).unwrap().to_path_buf())
+                .collect()
+        };
+        let strings = |items: &[&str]| items.iter().map(|item| item.to_string()).collect();
+
+        let rust = search(WalkOptions { types: strings(&["rust"]), ..Default::default() });
+        let not_js = search(WalkOptions { types_not: strings(&["js"]), ..Default::default() });
+        let globbed = search(WalkOptions { globs: strings(&["*.rs", "!src/gen/**"]), ..Default::default() });
+        let added = search(WalkOptions {
+            type_defs: strings(&["docs:*.md"]),
+            types: strings(&["docs"]),
+            ..Default::default()
+        });
+        fs::remove_dir_all(&root).unwrap();
+
+        let paths = |items: &[&str]| items.iter().map(PathBuf::from).collect::<Vec<_>>();
+        assert_eq!(paths(&["src/gen/table.rs", "src/main.rs"]), rust);
+        assert_eq!(paths(&["README.md", "src/gen/table.rs", "src/main.rs"]), not_js);
+        assert_eq!(paths(&["src/main.rs"]), globbed);
+        assert_eq!(paths(&["README.md"]), added);
+
+        let unknown = WalkOptions { types: strings(&["cobol-ish"]), ..Default::default() };
+        assert!(unknown.check().is_err());
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, prelude::*, BufReader, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use memchr::{memchr_iter, memrchr};
+
+use {collect_files, search_to, Failed};
+use config::Config;
+use error::{report, Error};
+use input;
+use printer::{self, Stats};
+use search::{Event, Query};
+use searcher::Searcher;
+
+/// How often the files are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How much is read at a time looking back for the last complete line.
+const SCAN_LEN: usize = 8 * 1024;
+
+/// How much of a file has been searched.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Progress {
+    /// Length and modification time when the file was last looked at.
+    len: u64,
+    modified: Option<SystemTime>,
+    /// Bytes and lines searched so far: up to the end of the last complete
+    /// line, as a line still being written is searched once it is done.
+    offset: u64,
+    lines: usize,
+    /// Lines selected so far, for `--max-count`.
+    selected: usize,
+}
+
+/// Searches the files of a `Config` again and again, each time only what
+/// changed since the time before, for `--watch`.
+pub struct Watcher<'a> {
+    query: &'a Query,
+    config: &'a Config,
+    with_filename: bool,
+    files: BTreeMap<PathBuf, Progress>,
+}
+
+impl<'a> Watcher<'a> {
+    pub fn new(query: &'a Query, config: &'a Config) -> Watcher<'a> {
+        let with_filename = !matches!(config.paths.as_slice(), [ref path] if !Path::new(path).is_dir());
+
+        Watcher { query, config, with_filename, files: BTreeMap::new() }
+    }
+
+    /// Search what was added to each file since the last poll, or all of
+    /// it if the file is new, shrank or was rewritten. Files that show up
+    /// in a watched directory are picked up too.
+    pub fn poll<W: Write>(&mut self, out: &mut W) -> Result<Stats, Error> {
+        let files = collect_files(self.config)?;
+        let mut total = Stats::default();
+
+        // Forget files that went away, so they are searched from the start
+        // if they come back (e.g. a rotated log).
+        self.files.retain(|path, _| files.iter().any(|(file, _)| file == path));
+
+        for (path, walked) in files {
+            total.add(self.poll_file(&path, walked, out)?);
+        }
+
+        Ok(total)
+    }
+
+    fn poll_file<W: Write>(&mut self, path: &Path, walked: bool, out: &mut W) -> io::Result<Stats> {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(_) => {
+                self.files.remove(path);
+                return Ok(Stats::default());
+            },
+        };
+        let (len, modified) = (metadata.len(), metadata.modified().ok());
+
+        let mut progress = match self.files.get(path) {
+            Some(&progress) if progress.len == len && progress.modified == modified => return Ok(Stats::default()),
+            Some(&progress) if len > progress.len => progress,
+            // New, truncated or rewritten in place.
+            _ => Progress::default(),
+        };
+        progress.len = len;
+        progress.modified = modified;
+
+        match self.search_complete_lines(path, walked, &mut progress, out) {
+            Ok(stats) => {
+                self.files.insert(path.to_path_buf(), progress);
+                Ok(stats)
+            },
+            // Output can't be written; nothing more to do.
+            Err(Failed::Writing(e)) => Err(e),
+            Err(Failed::Reading(e)) => {
+                // Tried again once the file changes.
+                self.files.insert(path.to_path_buf(), Progress { len, modified, ..Progress::default() });
+                eprintln!("minigrep: {}", report(&Error::reading(path, e)));
+                Ok(Stats::error())
+            },
+        }
+    }
+
+    /// Search the complete lines after `progress.offset`, streaming them
+    /// from the file, and move `progress` past them.
+    fn search_complete_lines<W: Write>(&self, path: &Path, walked: bool, progress: &mut Progress,
+                                       out: &mut W) -> Result<Stats, Failed> {
+        let mut file = File::open(path).map_err(Failed::Reading)?;
+        let end = last_line_end(&mut file, progress.offset, progress.len).map_err(Failed::Reading)?;
+        if end == progress.offset {
+            return Ok(Stats::default());
+        }
+
+        file.seek(SeekFrom::Start(progress.offset)).map_err(Failed::Reading)?;
+        let mut reader = BufReader::new(CountLines { inner: file.take(end - progress.offset), lines: 0 });
+        let binary = !self.config.text && input::is_binary(reader.fill_buf().map_err(Failed::Reading)?);
+
+        let stats = if walked && binary {
+            Stats::default()
+        } else {
+            self.search(path, &mut reader, binary, progress, out)?
+        };
+        // Whatever `--max-count` left unread still has to be counted.
+        io::copy(&mut reader, &mut io::sink()).map_err(Failed::Reading)?;
+
+        progress.offset = end;
+        progress.lines += reader.get_ref().lines;
+        progress.selected += stats.matched_lines;
+
+        Ok(stats)
+    }
+
+    /// Search `reader`, which starts `progress` into the file at `path`.
+    fn search<R: BufRead, W: Write>(&self, path: &Path, reader: R, binary: bool, progress: &Progress,
+                                    out: &mut W) -> Result<Stats, Failed> {
+        let config = self.config;
+        let max_count = config.max_count.map(|max| max.saturating_sub(progress.selected));
+        if max_count == Some(0) {
+            return Ok(Stats::default());
+        }
+        let (lines, offset) = (progress.lines, progress.offset as usize);
+
+        let searcher = Searcher::new()
+            .before_context(config.before_context)
+            .after_context(config.after_context)
+            .invert_match(config.invert_match)
+            .max_count(max_count);
+        let mut printer = printer::for_config(config, self.query, &mut *out, self.with_filename);
+
+        printer.begin(path, binary).map_err(Failed::Writing)?;
+        let result = search_to(&searcher, self.query, reader, &mut |event: &Event| {
+            printer.event(path, &event.shifted(lines, offset))
+        });
+        let stats = printer.end(path).map_err(Failed::Writing)?;
+
+        result.map(|()| stats)
+    }
+}
+
+/// Where the last complete line before `len` in `file` ends, looking no
+/// further back than `offset`, which is returned if there is none.
+fn last_line_end(file: &mut File, offset: u64, len: u64) -> io::Result<u64> {
+    let mut block = [0; SCAN_LEN];
+    let mut end = len;
+
+    while end > offset {
+        let start = end.saturating_sub(SCAN_LEN as u64).max(offset);
+        let block = &mut block[..(end - start) as usize];
+
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(block)?;
+        if let Some(i) = memrchr(b'\n', block) {
+            return Ok(start + i as u64 + 1);
+        }
+        end = start;
+    }
+
+    Ok(offset)
+}
+
+/// Counts the line terminators read through it.
+struct CountLines<R> {
+    inner: R,
+    lines: usize,
+}
+
+impl<R: Read> Read for CountLines<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.lines += memchr_iter(b'\n', &buf[..len]).count();
+        Ok(len)
+    }
+}
+
+/// Poll the files of `config` until writing to `out` fails, which is the
+/// only way this returns.
+pub fn watch<W: Write>(query: &Query, config: &Config, out: &mut W) -> Result<(), Error> {
+    let mut watcher = Watcher::new(query, config);
+
+    loop {
+        watcher.poll(out)?;
+        out.flush()?;
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+    use std::fs::OpenOptions;
+    use std::process;
+
+    #[test]
+    fn searches_appended_lines_and_rewritten_files() {
+        let dir = env::temp_dir().join(format!("minigrep-watch-{}", process::id()));
+        let log = dir.join("app.log");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&log, "start\nerror: disk full\nerror: half a li").unwrap();
+
+        let config = Config {
+            patterns: vec!["error".to_string()],
+            paths: vec![log.to_string_lossy().into_owned()],
+            line_number: true,
+            byte_offset: true,
+            ..Default::default()
+        };
+        let query = Query::new(&config).unwrap();
+        let mut watcher = Watcher::new(&query, &config);
+        let mut poll = || {
+            let mut out = Vec::new();
+            watcher.poll(&mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        // The line still being written waits until it is complete.
+        assert_eq!("2:6:error: disk full\n", poll());
+        assert_eq!("", poll());
+
+        OpenOptions::new().append(true).open(&log).unwrap().write_all(b"ne\nok\nerror: again\n").unwrap();
+        assert_eq!("3:23:error: half a line\n5:45:error: again\n", poll());
+
+        fs::write(&log, "error: rotated\n").unwrap();
+        assert_eq!("1:0:error: rotated\n", poll());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}